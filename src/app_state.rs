@@ -27,6 +27,9 @@ limitations under the License.
 
 const DEBUG : bool = false;
 
+// How often files of buffers are checked for changes made by someone else.
+const SYNC_CHECK_INTERVAL_MS : u64 = 1000;
+
 use ignore::gitignore;
 use serde_json;
 use std::env;
@@ -34,23 +37,36 @@ use std::fs;
 use std::path::Path;
 use std::sync::Arc;
 
+use buffer_state::BufferKind;
 use buffer_state::BufferOpenMode;
 use buffer_state::BufferState;
 use buffer_state::BufferStateS;
 use buffer_state_observer::BufferStateObserver;
+use clipboard_backend::{clipboard_backend_from_name, ClipboardBackend};
 use fuzzy_index::FuzzyIndex;
 use fuzzy_index_trait::FuzzyIndexTrait;
 use fuzzy_view_item::file_list_to_items;
+use kill_ring::KillRing;
+use lsp_client::LspStatus;
+use notifications::Notifications;
+use project_search::{
+    changed_results, file_replacements, search_text, FileReplacements, ProjectMatch,
+    ProjectSearchIndex,
+};
+use search::{build_regex, replacement_events, SearchOptions};
 
 use content_provider;
+use content_provider::EditEvent;
 use content_provider::RopeBasedContentProvider;
 use cursive;
 use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
+use std::cmp;
 use std::error;
 use std::io;
 use std::io::Write;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use buffer_id::BufferId;
 use buffer_index::BufferIndex;
@@ -59,7 +75,10 @@ use core::borrow::Borrow;
 use dir_tree::LazyTreeNode;
 use dir_tree::TreeNode;
 use dir_tree::TreeNodeRef;
+use regex;
+use ropey::Rope;
 use settings::Settings;
+use text_navigation::line_end_without_newline;
 use std::cell::Cell;
 use std::collections::VecDeque;
 use std::io::Error;
@@ -69,9 +88,21 @@ use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
 use view_handle::ViewHandle;
 
+/// Outcome of writing rows of search results back to their files.
+pub struct SearchResultsWriteBack {
+    pub num_written :  usize,
+    pub num_skipped :  usize,
+    /// Changes of files opened in buffers. They are not applied by AppState, as cursors of views
+    /// showing these buffers have to be mapped through them.
+    pub buffer_edits : Vec<(BufferId, Vec<EditEvent>)>,
+    /// First error of writing a file that is not opened, if any. Rows of that file are skipped.
+    pub error_op :     Option<io::Error>,
+}
+
 pub struct AppState {
     buffers_to_load : VecDeque<PathBuf>,
     file_index :      Arc<RefCell<FuzzyIndex>>,
+    file_list :       Arc<Vec<PathBuf>>, // files of file_index, searched by project search.
     /* because searches are mutating the cache TODO this can be solved with "interior
      * mutability", as other caches in this app */
     dir_and_files_tree :     TreeNodeRef,
@@ -79,8 +110,14 @@ pub struct AppState {
     directories :            Vec<PathBuf>,
     /* it's a straigthforward copy of arguments used to guess "workspace" parameter for
      * languageserver */
-    loaded_buffers : HashMap<BufferId, Rc<RefCell<BufferState>>>,
-    settings :       Rc<RefCell<Settings>>,
+    loaded_buffers :  HashMap<BufferId, Rc<RefCell<BufferState>>>,
+    settings :        Rc<RefCell<Settings>>,
+    kill_ring :       Rc<RefCell<KillRing>>, // shared by all text views
+    clipboard :       Rc<RefCell<Box<ClipboardBackend>>>,
+    lsp_status :      Rc<Cell<LspStatus>>, // shown by all text views
+    notifications :   Rc<RefCell<Notifications>>,
+    log_file :        Option<PathBuf>, // None if logging to file is off
+    last_sync_check : Instant,
 }
 
 impl AppState {
@@ -113,6 +150,12 @@ impl AppState {
         result
     }
 
+    /// Returns a fresh index searching contents of all indexed files, to be used with
+    /// FuzzyQueryView.
+    pub fn project_search_index(&self) -> Arc<RefCell<ProjectSearchIndex>> {
+        Arc::new(RefCell::new(ProjectSearchIndex::new(self.file_list.clone())))
+    }
+
     /// Returns file index. Rather stable.
     pub fn get_file_index(&self) -> Arc<RefCell<FuzzyIndexTrait>> {
         self.file_index.clone()
@@ -159,11 +202,310 @@ impl AppState {
         // TODO(njskalski): add delayed load (promise)
         let autohighlight : bool = self.settings_ref().auto_highlighting_enabled();
         let buffer = BufferState::open(path, ExistPolicy::MustExist)?;
+        let budget = self.settings_ref().history_memory_budget();
+        (*buffer).borrow_mut().set_history_memory_budget(Some(budget));
+        let id = (*buffer).borrow().id();
+        self.loaded_buffers.insert(id.clone(), buffer);
+        Ok(id)
+    }
+
+    /// Returns buffer with log file, opening it read-only if it's not opened yet.
+    pub fn open_log(&mut self) -> Result<BufferId, io::Error> {
+        let path = match self.log_file {
+            Some(ref path) => path.clone(),
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "Logging to file is off."))
+            }
+        };
+
+        if let Some(buffer_id) = self.get_buffers_for_path(&path).first() {
+            return Ok(buffer_id.clone());
+        }
+
+        let buffer = BufferState::open_read_only(&path)?;
         let id = (*buffer).borrow().id();
         self.loaded_buffers.insert(id.clone(), buffer);
         Ok(id)
     }
 
+    /// Checks whether files of buffers were changed on disk, at most once per SYNC_CHECK_INTERVAL.
+    pub fn check_buffers_sync(&mut self) {
+        if self.last_sync_check.elapsed() < Duration::from_millis(SYNC_CHECK_INTERVAL_MS) {
+            return;
+        }
+        self.last_sync_check = Instant::now();
+
+        for buffer in self.loaded_buffers.values() {
+            (**buffer).borrow_mut().check_sync();
+        }
+    }
+
+    /// Follows changes on disk of given read-only buffers, like the log. Returns ids of buffers
+    /// that changed, with number of chars they had before.
+    pub fn reload_read_only_buffers(&mut self, ids : &Vec<BufferId>) -> Vec<(BufferId, usize)> {
+        let mut reloaded : Vec<(BufferId, usize)> = Vec::new();
+        for id in ids {
+            let mut buffer = match self.loaded_buffers.get(id) {
+                Some(buffer) => (**buffer).borrow_mut(),
+                None => continue,
+            };
+            if !buffer.is_read_only() {
+                continue;
+            }
+
+            let old_len_chars = buffer.get_content().get_lines().len_chars();
+            match buffer.reload_if_out_of_sync() {
+                Ok(true) => reloaded.push((id.clone(), old_len_chars)),
+                Ok(false) => {}
+                Err(e) => debug!("unable to reload {:?}, because \"{}\"", buffer.get_path(), e),
+            }
+        }
+        reloaded
+    }
+
+    /// Returns content of file, taken from buffer if it's opened, read from disk otherwise.
+    fn file_content(&self, path : &PathBuf) -> Result<Rope, io::Error> {
+        match self.get_buffers_for_path(path).first() {
+            Some(buffer_id) => {
+                Ok((*self.loaded_buffers[buffer_id]).borrow().get_content().get_lines().clone())
+            }
+            None => Rope::from_reader(fs::File::open(path)?),
+        }
+    }
+
+    /// Finds all lines matching query in indexed files, synchronously. Files opened in buffers are
+    /// searched in buffers.
+    pub fn search_project(
+        &self,
+        query : &str,
+        options : &SearchOptions,
+    ) -> Result<Vec<ProjectMatch>, regex::Error> {
+        let regex = build_regex(query, options)?;
+        let mut result : Vec<ProjectMatch> = Vec::new();
+
+        for path in self.file_list.iter() {
+            match self.file_content(path) {
+                Ok(rope) => result.append(&mut search_text(path, &rope, &regex)),
+                Err(e) => debug!("project search: skipping {:?}, because \"{}\"", path, e),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Creates buffer listing search results, returns it's id.
+    pub fn open_search_results(&mut self, matches : Vec<ProjectMatch>) -> BufferId {
+        let buffer = BufferState::new_search_results(matches);
+        let budget = self.settings_ref().history_memory_budget();
+        (*buffer).borrow_mut().set_history_memory_budget(Some(budget));
+        let id = (*buffer).borrow().id();
+        self.loaded_buffers.insert(id.clone(), buffer);
+        id
+    }
+
+    pub fn is_search_results(&self, id : &BufferId) -> bool {
+        match self.loaded_buffers.get(id) {
+            Some(buffer) => match (**buffer).borrow().kind() {
+                &BufferKind::SearchResults(_) => true,
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Writes rows of search results buffer that were modified back to their files: directly to
+    /// disk for files that are not opened, for opened ones edits are returned (see
+    /// SearchResultsWriteBack). Lines that changed since search, and files opened read-only, are
+    /// skipped, and buffer stays modified then.
+    pub fn save_search_results(
+        &mut self,
+        id : &BufferId,
+    ) -> Result<SearchResultsWriteBack, io::Error> {
+        let (text, mut matches) = {
+            let buffer = (**self.loaded_buffers.get(id).unwrap()).borrow();
+            match buffer.kind() {
+                &BufferKind::SearchResults(ref matches) => {
+                    (buffer.get_content().get_lines().to_string(), matches.clone())
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "not a search results buffer",
+                    ))
+                }
+            }
+        };
+
+        // grouping changes by file, in order of appearance.
+        let mut changes_by_path : Vec<(PathBuf, Vec<(ProjectMatch, String)>)> = Vec::new();
+        for (original, new_text) in changed_results(&text, &matches) {
+            match changes_by_path.iter().position(|&(ref path, _)| *path == original.path) {
+                Some(idx) => changes_by_path[idx].1.push((original, new_text)),
+                None => changes_by_path.push((original.path.clone(), vec![(original, new_text)])),
+            }
+        }
+
+        let num_changed : usize =
+            changes_by_path.iter().map(|&(_, ref changes)| changes.len()).sum();
+        let mut num_written : usize = 0;
+        let mut buffer_edits : Vec<(BufferId, Vec<EditEvent>)> = Vec::new();
+        let mut error_op : Option<io::Error> = None;
+        for (path, changes) in changes_by_path {
+            match self.write_back_lines(&path, &changes) {
+                Ok((written, buffer_edit_op)) => {
+                    buffer_edits.extend(buffer_edit_op);
+                    for idx in written {
+                        let (ref original, ref new_text) = changes[idx];
+                        for m in matches.iter_mut() {
+                            if m.path == original.path && m.line == original.line {
+                                m.text = new_text.clone();
+                            }
+                        }
+                        num_written += 1;
+                    }
+                }
+                Err(e) => {
+                    error!("unable to write search results back to {:?}: {}", path, e);
+                    if error_op.is_none() {
+                        error_op = Some(e);
+                    }
+                }
+            }
+        }
+
+        let num_skipped = num_changed - num_written;
+        (**self.loaded_buffers.get(id).unwrap())
+            .borrow_mut()
+            .search_results_saved(matches, num_skipped == 0);
+        Ok(SearchResultsWriteBack { num_written, num_skipped, buffer_edits, error_op })
+    }
+
+    // Replaces lines of a single file, returns indices of changes that were applied. If file is
+    // opened, it's left untouched and events for it's buffer are returned along.
+    fn write_back_lines(
+        &mut self,
+        path : &PathBuf,
+        changes : &Vec<(ProjectMatch, String)>,
+    ) -> Result<(Vec<usize>, Option<(BufferId, Vec<EditEvent>)>), io::Error> {
+        if let Some(buffer_id) = self.get_buffers_for_path(path).first() {
+            if (*self.loaded_buffers[buffer_id]).borrow().is_read_only() {
+                error!("{:?} is opened read-only, skipping.", path);
+                return Ok((Vec::new(), None));
+            }
+        }
+
+        let mut rope = self.file_content(path)?;
+
+        // from the last line, so offsets of events stay valid.
+        let mut order : Vec<usize> = (0..changes.len()).collect();
+        order.sort_by_key(|&idx| cmp::Reverse(changes[idx].0.line));
+
+        let mut written : Vec<usize> = Vec::new();
+        let mut events : Vec<EditEvent> = Vec::new();
+        for idx in order {
+            let (ref original, ref new_text) = changes[idx];
+            if original.line >= rope.len_lines() {
+                error!("{:?} has no line {} anymore, skipping.", path, original.line + 1);
+                continue;
+            }
+
+            let begin = rope.line_to_char(original.line);
+            let end = line_end_without_newline(&rope, original.line);
+            if rope.slice(begin..end).to_string() != original.text {
+                error!("{:?}:{} changed since search, skipping.", path, original.line + 1);
+                continue;
+            }
+
+            events.push(EditEvent::Change {
+                offset :  begin,
+                length :  end - begin,
+                content : new_text.clone(),
+            });
+            written.push(idx);
+        }
+
+        if events.is_empty() {
+            return Ok((written, None));
+        }
+
+        if let Some(buffer_id) = self.get_buffers_for_path(path).first() {
+            return Ok((written, Some((buffer_id.clone(), events))));
+        }
+
+        for event in &events {
+            if let &EditEvent::Change { offset, length, ref content } = event {
+                rope.remove(offset..offset + length);
+                rope.insert(offset, content);
+            }
+        }
+        rope.write_to(io::BufWriter::new(fs::File::create(path)?))?;
+
+        Ok((written, None))
+    }
+
+    /// Computes replacements of query in all indexed files. Files opened in buffers are searched
+    /// in buffers, as they can have unsaved changes.
+    pub fn project_replacements(
+        &self,
+        query : &str,
+        options : &SearchOptions,
+        replacement : &str,
+    ) -> Result<Vec<FileReplacements>, regex::Error> {
+        let regex = build_regex(query, options)?;
+        let mut result : Vec<FileReplacements> = Vec::new();
+
+        for path in self.file_list.iter() {
+            let rope = match self.file_content(path) {
+                Ok(rope) => rope,
+                Err(e) => {
+                    debug!("project replace: skipping {:?}, because \"{}\"", path, e);
+                    continue;
+                }
+            };
+
+            if let Some(file) = file_replacements(path, &rope, &regex, replacement, options.regex) {
+                result.push(file);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Turns selected replacements into events, opening files that are not opened yet. Every file
+    /// gets it's own events, so they can be applied (and undone) as a single batch. Read-only
+    /// buffers and files that changed since replacements were computed are skipped. Events are
+    /// not applied here, as cursors of views have to be mapped through them.
+    pub fn replacement_edits(
+        &mut self,
+        files : Vec<FileReplacements>,
+    ) -> Vec<(BufferId, Vec<EditEvent>)> {
+        let mut result : Vec<(BufferId, Vec<EditEvent>)> = Vec::new();
+
+        for file in files {
+            let buffer_id = match self.open_or_get_file(&file.path) {
+                Ok(buffer_id) => buffer_id,
+                Err(e) => {
+                    error!("unable to open {:?} for replace, because \"{}\"", file.path, e);
+                    continue;
+                }
+            };
+
+            let buffer = (**self.loaded_buffers.get(&buffer_id).unwrap()).borrow();
+            if buffer.is_read_only() {
+                error!("{:?} is opened read-only, skipping.", file.path);
+                continue;
+            }
+            if !file.matches_content(buffer.get_content().get_lines()) {
+                error!("{:?} changed since replacements were computed, skipping.", file.path);
+                continue;
+            }
+
+            result.push((buffer_id.clone(), replacement_events(file.selected_replacements())));
+        }
+
+        result
+    }
+
     /// This method is called while constructing interface, to determine content of first edit view.
     pub fn get_first_buffer(&mut self) -> Result<BufferStateObserver, io::Error> {
         if self.get_first_buffer_guard.get() {
@@ -179,13 +521,20 @@ impl AppState {
             BufferState::open(&file_path, ExistPolicy::CanExist)?
         };
 
+        let budget = self.settings_ref().history_memory_budget();
+        (*buffer).borrow_mut().set_history_memory_budget(Some(budget));
         let id = (*buffer).borrow().id();
         self.loaded_buffers.insert(id.clone(), buffer);
 
         Ok(self.buffer_obs(&id).unwrap())
     }
 
-    pub fn new(directories : Vec<PathBuf>, files : Vec<PathBuf>, enable_gitignore : bool) -> Self {
+    pub fn new(
+        directories : Vec<PathBuf>,
+        files : Vec<PathBuf>,
+        enable_gitignore : bool,
+        log_file : Option<PathBuf>,
+    ) -> Self {
         let mut files_to_index : Vec<PathBuf> = files.to_owned();
         debug!("enable_gitignore == {}", enable_gitignore);
         for dir in &directories {
@@ -198,15 +547,24 @@ impl AppState {
         let buffers_to_load : VecDeque<PathBuf> = files.iter().map(|x| x.clone()).collect();
 
         let settings = Rc::new(RefCell::new(Settings::load_default()));
+        let clipboard = clipboard_backend_from_name((*settings).borrow().clipboard_backend());
+        let notifications = Notifications::new((*settings).borrow().notification_timeout());
 
         AppState {
             buffers_to_load :        buffers_to_load,
             loaded_buffers :         HashMap::new(),
             file_index :             Arc::new(RefCell::new(FuzzyIndex::new(file_index_items))),
+            file_list :              Arc::new(files_to_index),
             dir_and_files_tree :     LazyTreeNode::new(directories.clone(), files).as_ref(),
             get_first_buffer_guard : Cell::new(false),
             directories :            directories,
             settings :               settings,
+            kill_ring :              Rc::new(RefCell::new(KillRing::new())),
+            clipboard :              Rc::new(RefCell::new(clipboard)),
+            lsp_status :             Rc::new(Cell::new(LspStatus::Off)),
+            notifications :          Rc::new(RefCell::new(notifications)),
+            log_file :               log_file,
+            last_sync_check :        Instant::now(),
         }
     }
 
@@ -221,6 +579,22 @@ impl AppState {
     pub fn settings_ref(&self) -> Ref<Settings> {
         (*self.settings).borrow()
     }
+
+    pub fn kill_ring_rc(&self) -> &Rc<RefCell<KillRing>> {
+        &self.kill_ring
+    }
+
+    pub fn clipboard_rc(&self) -> &Rc<RefCell<Box<ClipboardBackend>>> {
+        &self.clipboard
+    }
+
+    pub fn lsp_status_rc(&self) -> &Rc<Cell<LspStatus>> {
+        &self.lsp_status
+    }
+
+    pub fn notifications_rc(&self) -> &Rc<RefCell<Notifications>> {
+        &self.notifications
+    }
 }
 
 /// this method takes into account .git and other directives set in .gitignore. However it only
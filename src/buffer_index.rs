@@ -47,6 +47,7 @@ fn buffer_to_item(buffer : &BufferStateObserver) -> ViewItem {
 
     let header : String = match buffer.get_filename() {
         Some(filename) => format!("{}{}", filename.to_string_lossy(), if buffer.modified() { " *"} else { ""}),
+        None if buffer.is_search_results() => format!("<search results> {}{}", buffer.buffer_id(), if buffer.modified() { " *"} else { ""}),
         None => format!("<unnamed> {}{}", buffer.buffer_id(), if buffer.modified() { " *"} else { ""})
     };
 
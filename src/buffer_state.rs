@@ -14,22 +14,29 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use content_provider::EditBatch;
 use content_provider::EditEvent;
+use content_provider::RevisionId;
 use content_provider::RopeBasedContentProvider;
 use view_handle::ViewHandle;
 
 use cursive;
 use std::fs;
 use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::str;
 
 use std::cell::RefCell;
 use std::env;
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::SystemTime;
 
 use buffer_id::BufferId;
 use buffer_state_observer::BufferStateObserver;
+use cursor::Cursor;
+use project_search::{results_to_text, ProjectMatch};
 use std::borrow::Borrow;
 use utils::highlight_settings_from_path;
 
@@ -46,6 +53,16 @@ pub enum ExistPolicy {
     MustNotExist,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BufferKind {
+    /// Regular buffer, saved to it's path (unnamed ones get path on first save).
+    File,
+    /// Results of project search, one "path:line: text" row per match. Saving writes modified
+    /// rows back to files they were found in (see AppState::save_search_results). Holds matches
+    /// as they were found, or last written back.
+    SearchResults(Vec<ProjectMatch>),
+}
+
 /// This struct represents serializable part of BufferState.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BufferStateS {
@@ -57,7 +74,10 @@ pub struct BufferState {
     id :           BufferId,
     ss :           BufferStateS,
     modified :     bool,
+    sync_time :    Option<SystemTime>, // modification time of file when loaded or saved
+    out_of_sync :  bool,               // as of last check_sync
     mode :         BufferOpenMode,
+    kind :         BufferKind,
     content :      RopeBasedContentProvider,
 }
 
@@ -67,8 +87,27 @@ impl BufferState {
             id :           BufferId::new(),
             ss :           BufferStateS { path : None },
             modified :     false,
+            sync_time :    None,
+            out_of_sync :  false,
             content :      RopeBasedContentProvider::new(None, None),
             mode :         BufferOpenMode::ReadWrite,
+            kind :         BufferKind::File,
+        }))
+    }
+
+    pub fn new_search_results(matches : Vec<ProjectMatch>) -> Rc<RefCell<Self>> {
+        let text = results_to_text(&matches);
+        let mut reader : &[u8] = text.as_bytes();
+
+        Rc::new(RefCell::new(BufferState {
+            id :           BufferId::new(),
+            ss :           BufferStateS { path : None },
+            modified :     false,
+            sync_time :    None,
+            out_of_sync :  false,
+            content :      RopeBasedContentProvider::new(Some(&mut reader), None),
+            mode :         BufferOpenMode::ReadWrite,
+            kind :         BufferKind::SearchResults(matches),
         }))
     }
 
@@ -76,6 +115,24 @@ impl BufferState {
         self.modified
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.mode == BufferOpenMode::ReadOnly
+    }
+
+    /// Returns whether file was changed (or removed) by someone else since it was loaded or last
+    /// saved, as of the last check_sync. Cheap, so it can be called while drawing.
+    pub fn out_of_sync(&self) -> bool {
+        self.out_of_sync
+    }
+
+    /// Compares modification time of file with the one it had when loaded or last saved.
+    pub fn check_sync(&mut self) {
+        self.out_of_sync = match (self.sync_time, self.ss.path.as_ref()) {
+            (Some(sync_time), Some(path)) => file_modification_time(path) != Some(sync_time),
+            _ => false,
+        };
+    }
+
     pub fn id(&self) -> BufferId {
         self.id.clone()
     }
@@ -107,11 +164,69 @@ impl BufferState {
             id :           BufferId::new(),
             ss :           BufferStateS { path : Some(file_path.to_owned()) },
             modified :     false,
+            sync_time :    file_modification_time(file_path),
+            out_of_sync :  false,
             content :      RopeBasedContentProvider::new(Some(&mut reader), highlight_settings_op),
             mode :         BufferOpenMode::ReadWrite,
+            kind :         BufferKind::File,
         })))
     }
 
+    /// Opens existing file in a buffer that can't be edited, but follows changes of the file (see
+    /// reload_if_out_of_sync).
+    pub fn open_read_only(file_path : &Path) -> Result<Rc<RefCell<Self>>, io::Error> {
+        let buffer = Self::open(file_path, ExistPolicy::MustExist)?;
+        (*buffer).borrow_mut().mode = BufferOpenMode::ReadOnly;
+        Ok(buffer)
+    }
+
+    /// Follows changes of file on disk. If it only grew, new bytes are appended to content,
+    /// otherwise it's re-read, dropping history. Returns whether content changed.
+    pub fn reload_if_out_of_sync(&mut self) -> Result<bool, io::Error> {
+        self.check_sync();
+        if !self.out_of_sync {
+            return Ok(false);
+        }
+
+        let path = self.ss.path.clone().unwrap();
+        let mut file = fs::File::open(&path)?;
+        let loaded_bytes = self.content.get_lines().len_bytes() as u64;
+        if file.metadata()?.len() >= loaded_bytes {
+            let mut new_bytes : Vec<u8> = Vec::new();
+            file.seek(SeekFrom::Start(loaded_bytes))?;
+            file.read_to_end(&mut new_bytes)?;
+            // char cut in half by writer gets completed on next reload.
+            let valid_len = match str::from_utf8(&new_bytes) {
+                Ok(_) => new_bytes.len(),
+                Err(e) => e.valid_up_to(),
+            };
+            let text = str::from_utf8(&new_bytes[..valid_len]).unwrap();
+            self.content.append_without_history(text);
+        } else {
+            let highlight_settings_op = highlight_settings_from_path(&path);
+            self.content = RopeBasedContentProvider::new(Some(&mut file), highlight_settings_op);
+        }
+
+        self.sync_time = file_modification_time(&path);
+        self.out_of_sync = false;
+        self.modified = false;
+        Ok(true)
+    }
+
+    pub fn kind(&self) -> &BufferKind {
+        &self.kind
+    }
+
+    /// Called once modified rows of search results were written back. Given matches become the
+    /// new reference for finding modified rows. Buffer stays modified if not all rows were
+    /// written.
+    pub fn search_results_saved(&mut self, matches : Vec<ProjectMatch>, all_written : bool) {
+        self.kind = BufferKind::SearchResults(matches);
+        if all_written {
+            self.modified = false;
+        }
+    }
+
     pub fn get_content(&self) -> &RopeBasedContentProvider {
         &self.content
     }
@@ -120,11 +235,47 @@ impl BufferState {
         &mut self.content
     }
 
-    pub fn submit_edit_events(&mut self, events : Vec<EditEvent>) {
-        self.content.submit_events(events);
+    pub fn submit_edit_events(&mut self, batch : EditBatch) {
+        self.content.submit_events(batch);
         self.modified = true; // TODO modified should be moved to history.
     }
 
+    pub fn undo(&mut self) -> Option<(Vec<EditEvent>, Vec<Cursor>)> {
+        let result = self.content.undo();
+        if result.is_some() {
+            self.modified = true;
+        }
+        result
+    }
+
+    pub fn redo(&mut self) -> Option<(Vec<EditEvent>, Vec<Cursor>)> {
+        let result = self.content.redo();
+        if result.is_some() {
+            self.modified = true;
+        }
+        result
+    }
+
+    pub fn set_history_memory_budget(&mut self, budget_op : Option<usize>) {
+        self.content.set_history_memory_budget(budget_op)
+    }
+
+    /// Returns estimated number of bytes used by history of this buffer.
+    pub fn history_memory_usage(&self) -> usize {
+        self.content.history_memory_usage()
+    }
+
+    pub fn jump_to_revision(
+        &mut self,
+        id : RevisionId,
+    ) -> Option<(Vec<EditEvent>, Vec<Cursor>)> {
+        let result = self.content.jump_to_revision(id);
+        if result.is_some() {
+            self.modified = true;
+        }
+        result
+    }
+
     pub fn get_filename(&self) -> Option<OsString> {
         match self.ss.path {
             Some(ref path) => path.file_name().map(|osstr| osstr.to_os_string()),
@@ -150,6 +301,10 @@ impl BufferState {
             return Err(io::Error::new(io::ErrorKind::NotFound, "No path provided."));
         }
 
+        if self.is_read_only() && (path.is_none() || path == self.ss.path) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Buffer is read-only."));
+        }
+
         if path == self.ss.path && self.exists() && !self.modified {
             info!("Early exit from BufferState.save - file not modified.");
             return Ok(());
@@ -163,6 +318,8 @@ impl BufferState {
         let mut file = fs::File::create(&final_path)?;
         self.proceed_with_save(file)?;
 
+        self.sync_time = file_modification_time(&final_path);
+        self.out_of_sync = false;
         self.ss.path = Some(final_path);
 
         self.modified = false;
@@ -171,6 +328,10 @@ impl BufferState {
     }
 }
 
+fn file_modification_time(path : &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
 fn path_to_reader(path : &Path) -> fs::File {
     fs::File::open(path).expect(&format!("file {:?} did not exist!", path))
 }
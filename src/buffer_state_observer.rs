@@ -21,13 +21,14 @@ use std::rc::Rc;
 use std::ffi::OsString;
 use std::path::PathBuf;
 
-use buffer_state::BufferState;
+use buffer_state::{BufferKind, BufferState};
 use content_provider::RopeBasedContentProvider;
 
 use buffer_id::BufferId;
 use content_provider;
 use cursive;
 use rich_content::RichContent;
+use cursor::Cursor;
 use std::cell::RefMut;
 use view_handle::ViewHandle;
 
@@ -44,7 +45,15 @@ impl BufferStateObserver {
     }
 
     pub fn modified(&self) -> bool {
-        self.borrow_state().modified()
+        self.buffer_state.borrow().modified()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.buffer_state.borrow().is_read_only()
+    }
+
+    pub fn out_of_sync(&self) -> bool {
+        self.buffer_state.borrow().out_of_sync()
     }
 
     pub fn buffer_id(&self) -> BufferId {
@@ -69,11 +78,37 @@ impl BufferStateObserver {
         self.buffer_state.borrow().get_path()
     }
 
+    pub fn is_search_results(&self) -> bool {
+        match self.buffer_state.borrow().kind() {
+            &BufferKind::SearchResults(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn get_filename(&self) -> Option<OsString> {
         self.buffer_state.borrow().get_filename()
     }
 
-    pub fn submit_edit_events_to_buffer(&self, events : Vec<content_provider::EditEvent>) {
-        self.buffer_state.borrow_mut().submit_edit_events(events)
+    pub fn submit_edit_events_to_buffer(&self, batch : content_provider::EditBatch) {
+        self.buffer_state.borrow_mut().submit_edit_events(batch)
+    }
+
+    pub fn undo(&self) -> Option<(Vec<content_provider::EditEvent>, Vec<Cursor>)> {
+        self.buffer_state.borrow_mut().undo()
+    }
+
+    pub fn redo(&self) -> Option<(Vec<content_provider::EditEvent>, Vec<Cursor>)> {
+        self.buffer_state.borrow_mut().redo()
+    }
+
+    pub fn history_memory_usage(&self) -> usize {
+        self.buffer_state.borrow().history_memory_usage()
+    }
+
+    pub fn jump_to_revision(
+        &self,
+        id : content_provider::RevisionId,
+    ) -> Option<(Vec<content_provider::EditEvent>, Vec<Cursor>)> {
+        self.buffer_state.borrow_mut().jump_to_revision(id)
     }
 }
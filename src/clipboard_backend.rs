@@ -0,0 +1,228 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Clipboard backends. Text views do not talk to system clipboard directly, because there is more
+// than one way to reach it:
+// - "system" uses clipboard crate, requires X (or other platform clipboard) to be reachable.
+// - "osc52" emits OSC 52 escape sequence, so the terminal emulator on user's desktop puts the text
+//   into it's clipboard. Works over SSH and inside tmux (with set-clipboard on). It's write-only,
+//   paste falls back to kill ring (terminals send pasted text as key events anyway).
+// - "internal" does nothing, only kill ring is used.
+// - "auto" picks osc52 in SSH session, system if available and internal otherwise.
+
+use clipboard;
+use clipboard::ClipboardProvider;
+use std::env;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+
+pub trait ClipboardBackend {
+    fn name(&self) -> &'static str;
+
+    fn set_contents(&mut self, text : String) -> Result<(), String>;
+
+    /// Returns None if clipboard is empty, unreachable or write-only.
+    fn get_contents(&mut self) -> Option<String>;
+}
+
+pub struct SystemClipboard {
+    context : clipboard::ClipboardContext,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Option<Self> {
+        match clipboard::ClipboardProvider::new() {
+            Ok(context) => Some(SystemClipboard { context : context }),
+            Err(e) => {
+                info!("System clipboard unavailable: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn set_contents(&mut self, text : String) -> Result<(), String> {
+        self.context.set_contents(text).map_err(|e| format!("{:?}", e))
+    }
+
+    fn get_contents(&mut self) -> Option<String> {
+        match self.context.get_contents() {
+            Ok(text) => Some(text),
+            Err(e) => {
+                info!("Error while attempting to access clipboard: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+pub struct Osc52Clipboard {
+    tmux_passthrough : bool,
+}
+
+impl Osc52Clipboard {
+    pub fn new(tmux_passthrough : bool) -> Self {
+        Osc52Clipboard { tmux_passthrough }
+    }
+
+    // Writing to controlling terminal directly, so the sequence does not get mixed with whatever
+    // cursive keeps in stdout buffer.
+    fn emit(sequence : &str) -> io::Result<()> {
+        match OpenOptions::new().write(true).open("/dev/tty") {
+            Ok(mut tty) => {
+                tty.write_all(sequence.as_bytes())?;
+                tty.flush()
+            }
+            Err(_) => {
+                let stdout = io::stdout();
+                let mut handle = stdout.lock();
+                handle.write_all(sequence.as_bytes())?;
+                handle.flush()
+            }
+        }
+    }
+}
+
+impl ClipboardBackend for Osc52Clipboard {
+    fn name(&self) -> &'static str {
+        "osc52"
+    }
+
+    fn set_contents(&mut self, text : String) -> Result<(), String> {
+        let sequence = osc52_sequence(&text, self.tmux_passthrough);
+        Self::emit(&sequence).map_err(|e| format!("{:?}", e))
+    }
+
+    fn get_contents(&mut self) -> Option<String> {
+        None
+    }
+}
+
+pub struct InternalClipboard {}
+
+impl ClipboardBackend for InternalClipboard {
+    fn name(&self) -> &'static str {
+        "internal"
+    }
+
+    fn set_contents(&mut self, _text : String) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn get_contents(&mut self) -> Option<String> {
+        None
+    }
+}
+
+fn in_ssh_session() -> bool {
+    env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some()
+}
+
+fn in_tmux() -> bool {
+    env::var_os("TMUX").is_some()
+}
+
+/// Creates backend by name used in settings ("clipboard/backend").
+pub fn clipboard_backend_from_name(name : &str) -> Box<ClipboardBackend> {
+    let backend : Box<ClipboardBackend> = match name {
+        "system" => match SystemClipboard::new() {
+            Some(system) => Box::new(system),
+            None => Box::new(InternalClipboard {}),
+        },
+        "osc52" => Box::new(Osc52Clipboard::new(in_tmux())),
+        "internal" => Box::new(InternalClipboard {}),
+        "auto" => {
+            if in_ssh_session() {
+                Box::new(Osc52Clipboard::new(in_tmux()))
+            } else {
+                match SystemClipboard::new() {
+                    Some(system) => Box::new(system),
+                    None => Box::new(InternalClipboard {}),
+                }
+            }
+        }
+        other => {
+            error!("unknown clipboard backend \"{}\", using internal one.", other);
+            Box::new(InternalClipboard {})
+        }
+    };
+
+    info!("using {} clipboard backend", backend.name());
+    backend
+}
+
+/// Builds OSC 52 "set clipboard" sequence. tmux needs it wrapped in DCS passthrough, with every
+/// ESC inside doubled.
+pub fn osc52_sequence(text : &str, tmux_passthrough : bool) -> String {
+    let osc = format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes()));
+    if tmux_passthrough {
+        format!("\x1bPtmux;{}\x1b\\", osc.replace("\x1b", "\x1b\x1b"))
+    } else {
+        osc
+    }
+}
+
+const BASE64_ALPHABET : &'static [u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data : &[u8]) -> String {
+    let mut result = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0] as usize;
+        let b1 = if chunk.len() > 1 { chunk[1] as usize } else { 0 };
+        let b2 = if chunk.len() > 2 { chunk[2] as usize } else { 0 };
+
+        result.push(BASE64_ALPHABET[b0 >> 2] as char);
+        result.push(BASE64_ALPHABET[((b0 & 0x03) << 4) | (b1 >> 4)] as char);
+        if chunk.len() > 1 {
+            result.push(BASE64_ALPHABET[((b1 & 0x0f) << 2) | (b2 >> 6)] as char);
+        } else {
+            result.push('=');
+        }
+        if chunk.len() > 2 {
+            result.push(BASE64_ALPHABET[b2 & 0x3f] as char);
+        } else {
+            result.push('=');
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_correctly() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn osc52_is_wrapped_for_tmux() {
+        assert_eq!(osc52_sequence("foo", false), "\x1b]52;c;Zm9v\x07");
+        assert_eq!(osc52_sequence("foo", true), "\x1bPtmux;\x1b\x1b]52;c;Zm9v\x07\x1b\\");
+    }
+}
@@ -15,6 +15,7 @@ limitations under the License.
 */
 use ropey::Rope;
 use serde_json as sj;
+use std::collections::HashMap;
 use std::io;
 use std::io::{BufRead, Read};
 use std::rc::Rc;
@@ -25,32 +26,134 @@ use rich_content::HighlightSettings;
 use rich_content::RichContent;
 use rich_content::RichLine;
 use ropey::RopeSlice;
+use cursor::Cursor;
 
 const DEFAULT_BLANK : char = ' ';
 
+// Batches marked as "coalesce" are merged into previous revision only if they come within this
+// time. Otherwise a long typing session would end up as a single undo step.
+const COALESCE_TIMEOUT_MS : i64 = 2000;
+
+// Rope shares unchanged chunks between revisions, so a revision costs roughly the chunks that were
+// copied on edit, and not the whole text. This is the guess of cost of a single EditEvent (one leaf
+// of ~1kB and the path to it), on top of the inserted text itself.
+const MEMORY_PER_EDIT_ESTIMATE : usize = 1536;
+
 /// Represents a order to edit a content. Offsets are calculated in CHARS, not bytes.
 /// offset is the first character of selection, inclusive.
 //TODO(njskalski) secure against overlapping cursors!
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EditEvent {
     Insert { offset : usize, content : String },
     Change { offset : usize, length : usize, content : String },
 }
 
+/// A group of EditEvents that is applied (and undone) as a single step. Cursors are stored along,
+/// so undo and redo can restore them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditBatch {
+    pub events :         Vec<EditEvent>,
+    pub cursors_before : Vec<Cursor>,
+    pub cursors_after :  Vec<Cursor>,
+    /// If true, the batch can be merged into previous one, provided that one was also marked so
+    /// and cursors were not moved in between. Used to group consecutive typed characters.
+    pub coalesce : bool,
+}
+
+impl EditBatch {
+    /// Creates a batch that does not carry cursor information and is never merged.
+    pub fn new(events : Vec<EditEvent>) -> Self {
+        EditBatch { events, cursors_before : Vec::new(), cursors_after : Vec::new(), coalesce : false }
+    }
+}
+
+pub type RevisionId = usize;
+
+// What a revision did, as far as merging batches into it goes: typing is not merged with deleting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+fn edit_kind(events : &Vec<EditEvent>) -> EditKind {
+    let is_insert = |event : &EditEvent| match event {
+        &EditEvent::Insert { .. } => true,
+        &EditEvent::Change { .. } => false,
+    };
+    let is_delete = |event : &EditEvent| match event {
+        &EditEvent::Insert { .. } => false,
+        &EditEvent::Change { ref content, .. } => content.is_empty(),
+    };
+
+    if events.iter().all(is_insert) {
+        EditKind::Insert
+    } else if events.iter().all(is_delete) {
+        EditKind::Delete
+    } else {
+        EditKind::Other
+    }
+}
+
+/// Summary of a single revision, used to present history to user.
+#[derive(Debug, Clone)]
+pub struct RevisionInfo {
+    pub id :          RevisionId,
+    pub parent :      Option<RevisionId>,
+    pub timestamp :   time::Tm,
+    pub num_lines :   usize,
+    pub num_children : usize,
+    pub is_current :  bool,
+    /// Estimated number of bytes this revision adds to history.
+    pub memory_estimate : usize,
+}
+
+// A node of undo tree.
 #[derive(Debug)]
 struct RopeBasedContent {
     lines :     Rope,
     timestamp : time::Tm,
+    parent :    Option<RevisionId>,
+    children :  Vec<RevisionId>,
+    // Child that redo moves to: the one most recently created or visited.
+    redo_child : Option<RevisionId>,
+    // Cursors of the view that issued the change, before and after it was made.
+    cursors_before : Vec<Cursor>,
+    cursors_after :  Vec<Cursor>,
+    // Events that turned parent into this revision, and the ones turning it back. Empty for root.
+    events :         Vec<EditEvent>,
+    undo_events :    Vec<EditEvent>,
+    coalescable :    bool,
+    kind :           EditKind,
+    // Number of lines common with previous revision, used to invalidate RichContent.
+    num_common_lines : usize,
+    // Estimated number of bytes this revision does not share with its parent. Zero for root, as
+    // base text is not part of history.
+    memory_estimate : usize,
 }
 
 impl RopeBasedContent {
     pub fn new(reader_op : Option<&mut Read>) -> Self {
-        match reader_op {
-            Some(reader) => RopeBasedContent {
-                lines :     Rope::from_reader(reader).expect("failed to build rope from reader"), /* TODO(njskalski) error handling */
-                timestamp : time::now(),
-            },
-            None => RopeBasedContent { lines : Rope::new(), timestamp : time::now() },
+        let lines = match reader_op {
+            Some(reader) => Rope::from_reader(reader).expect("failed to build rope from reader"), /* TODO(njskalski) error handling */
+            None => Rope::new(),
+        };
+
+        RopeBasedContent {
+            lines :            lines,
+            timestamp :        time::now(),
+            parent :           None,
+            children :         Vec::new(),
+            redo_child :       None,
+            cursors_before :   Vec::new(),
+            cursors_after :    Vec::new(),
+            events :           Vec::new(),
+            undo_events :      Vec::new(),
+            coalescable :      false,
+            kind :             EditKind::Other,
+            num_common_lines : 0,
+            memory_estimate :  0,
         }
     }
 
@@ -60,21 +163,28 @@ impl RopeBasedContent {
 }
 
 pub struct RopeBasedContentProvider {
-    history : Vec<RopeBasedContent>,
-    current : usize,
+    // Undo tree. Making a change after undo starts a new branch instead of dropping the redo's,
+    // so no revision is ever lost.
+    history :          HashMap<RevisionId, RopeBasedContent>,
+    current :          RevisionId,
+    next_revision_id : RevisionId,
+    root :             RevisionId,
+    // In bytes. If exceeded, old revisions are compacted. None means unbounded history.
+    history_memory_budget : Option<usize>,
     // Contract: we do not version rich content. It doesn't make sense: redrawing screen
     // has a similar complexity to syntax highlighting, provided it's implemented properly.
     rich_content :          Option<RichContent>,
     highlight_settings_op : Option<Rc<HighlightSettings>>,
 }
 
-// Applies events to RopeBasedContent producing new one, and returning *number of lines common* to
-// both new and original contents.
+// Applies events to a Rope producing new one, and returning *number of lines common* to both new
+// and original contents, along with events that turn new contents back into original ones.
 // Now events are applied one after another in order they were issued.
 //TODO in some combinations offsets should be recomputed. But I expect no such combinations appear.
 // I should however check it just in case.
-fn apply_events(c : &RopeBasedContent, events : &Vec<EditEvent>) -> (RopeBasedContent, usize) {
-    let mut new_lines : Rope = c.lines.clone();
+fn apply_events(lines : &Rope, events : &Vec<EditEvent>) -> (Rope, usize, Vec<EditEvent>) {
+    let mut new_lines : Rope = lines.clone();
+    let mut undo_events : Vec<EditEvent> = Vec::with_capacity(events.len());
 
     // Offset is in CHARS, and since it's common, it's valid in both new and old contents.
     let mut first_change_pos = new_lines.len_chars();
@@ -84,11 +194,22 @@ fn apply_events(c : &RopeBasedContent, events : &Vec<EditEvent>) -> (RopeBasedCo
             &EditEvent::Insert { ref offset, ref content } => {
                 first_change_pos = std::cmp::min(first_change_pos, *offset);
                 new_lines.insert(*offset, content);
+                undo_events.push(EditEvent::Change {
+                    offset :  *offset,
+                    length :  content.chars().count(),
+                    content : String::new(),
+                });
             }
             &EditEvent::Change { ref offset, ref length, ref content } => {
                 first_change_pos = std::cmp::min(first_change_pos, *offset);
+                let removed = new_lines.slice(*offset..(*offset + *length)).to_string();
                 new_lines.remove(*offset..(*offset + *length));
                 new_lines.insert(*offset, content);
+                undo_events.push(EditEvent::Change {
+                    offset :  *offset,
+                    length :  content.chars().count(),
+                    content : removed,
+                });
             }
             _ => debug!("event {:?} not supported yet", event),
         }
@@ -101,7 +222,7 @@ fn apply_events(c : &RopeBasedContent, events : &Vec<EditEvent>) -> (RopeBasedCo
     let num_common_lines = if first_change_pos == 0 {
         0
     } else {
-        let line_of_first_change = c.lines.char_to_line(first_change_pos);
+        let line_of_first_change = lines.char_to_line(first_change_pos);
         if line_of_first_change > 0 {
             line_of_first_change - 1
         } else {
@@ -109,7 +230,9 @@ fn apply_events(c : &RopeBasedContent, events : &Vec<EditEvent>) -> (RopeBasedCo
         }
     };
 
-    (RopeBasedContent { lines : new_lines, timestamp : time::now() }, num_common_lines)
+    // undoing goes from the last event back.
+    undo_events.reverse();
+    (new_lines, num_common_lines, undo_events)
 }
 
 impl RopeBasedContentProvider {
@@ -118,8 +241,11 @@ impl RopeBasedContentProvider {
         highlight_settings_op : Option<Rc<HighlightSettings>>,
     ) -> Self {
         RopeBasedContentProvider {
-            history :               vec![RopeBasedContent::new(reader_op)],
+            history :               hashmap![0 => RopeBasedContent::new(reader_op)],
             current :               0,
+            next_revision_id :      1,
+            root :                  0,
+            history_memory_budget : None,
             rich_content :          None,
             highlight_settings_op : highlight_settings_op,
         }
@@ -145,16 +271,29 @@ impl RopeBasedContentProvider {
         self.rich_content.is_some()
     }
 
+    /// Returns name of syntax detected for content, if any.
+    pub fn syntax_name(&self) -> Option<&str> {
+        self.highlight_settings_op.as_ref().map(|s| s.syntax_name())
+    }
+
+    fn current_revision(&self) -> &RopeBasedContent {
+        &self.history[&self.current]
+    }
+
+    fn current_revision_mut(&mut self) -> &mut RopeBasedContent {
+        self.history.get_mut(&self.current).unwrap()
+    }
+
     pub fn get_lines(&self) -> &Rope {
-        &self.history[self.current].lines
+        &self.current_revision().lines
     }
 
     pub fn get_line(&self, line_no : usize) -> RopeSlice {
-        self.history[self.current].lines.line(line_no)
+        self.current_revision().lines.line(line_no)
     }
 
     pub fn len_lines(&self) -> usize {
-        self.history[self.current].lines.len_lines()
+        self.current_revision().lines.len_lines()
     }
 
     pub fn get_rich_line(&self, line_no : usize) -> Option<Rc<RichLine>> {
@@ -162,23 +301,306 @@ impl RopeBasedContentProvider {
     }
 
     pub fn can_undo(&self) -> bool {
-        self.current > 0
+        self.current_revision().parent.is_some()
     }
 
     pub fn can_redo(&self) -> bool {
-        self.current < self.history.len() - 1
+        self.current_revision().redo_child.is_some()
+    }
+
+    pub fn current_revision_id(&self) -> RevisionId {
+        self.current
+    }
+
+    /// Returns all revisions in the order they were created.
+    pub fn revisions(&self) -> Vec<RevisionInfo> {
+        let mut result : Vec<RevisionInfo> = self
+            .history
+            .iter()
+            .map(|(id, revision)| RevisionInfo {
+                id :           *id,
+                parent :       revision.parent,
+                timestamp :    revision.timestamp,
+                num_lines :    revision.lines.len_lines(),
+                num_children : revision.children.len(),
+                is_current :   *id == self.current,
+                memory_estimate : revision.memory_estimate,
+            })
+            .collect();
+        result.sort_by_key(|info| info.id);
+        result
+    }
+
+    pub fn submit_events(&mut self, batch : EditBatch) {
+        debug!("got events {:?}", batch.events);
+
+        let num_common_lines = if self.can_coalesce(&batch) {
+            let last = self.current_revision_mut();
+            let (new_lines, num_common_lines, mut undo_events) =
+                apply_events(&last.lines, &batch.events);
+            last.lines = new_lines;
+            last.timestamp = time::now();
+            last.num_common_lines = std::cmp::min(last.num_common_lines, num_common_lines);
+            last.memory_estimate +=
+                batch_memory_estimate(&batch) + removed_text_estimate(&undo_events);
+            last.cursors_after = batch.cursors_after;
+            last.events.extend(batch.events);
+            undo_events.extend(last.undo_events.drain(..));
+            last.undo_events = undo_events;
+            num_common_lines
+        } else {
+            let (new_lines, num_common_lines, undo_events) =
+                apply_events(&self.current_revision().lines, &batch.events);
+            let id = self.next_revision_id;
+            self.next_revision_id += 1;
+            let memory_estimate =
+                batch_memory_estimate(&batch) + removed_text_estimate(&undo_events);
+
+            self.history.insert(
+                id,
+                RopeBasedContent {
+                    lines :            new_lines,
+                    timestamp :        time::now(),
+                    parent :           Some(self.current),
+                    children :         Vec::new(),
+                    redo_child :       None,
+                    cursors_before :   batch.cursors_before,
+                    cursors_after :    batch.cursors_after,
+                    events :           batch.events,
+                    undo_events :      undo_events,
+                    coalescable :      batch.coalesce,
+                    kind :             edit_kind(&batch.events),
+                    num_common_lines : num_common_lines,
+                    memory_estimate :  memory_estimate,
+                },
+            );
+
+            {
+                let parent = self.current_revision_mut();
+                parent.children.push(id);
+                parent.redo_child = Some(id);
+            }
+            self.current = id;
+            num_common_lines
+        };
+
+        self.update_rich_content(num_common_lines);
+        self.compact_history();
+    }
+
+    /// Appends text to the end of current revision, without recording it in history. Meant for
+    /// read-only buffers following a file that only grows, like the log.
+    pub fn append_without_history(&mut self, text : &str) {
+        let old_len_lines = {
+            let lines = &mut self.current_revision_mut().lines;
+            let old_len_lines = lines.len_lines();
+            let len_chars = lines.len_chars();
+            lines.insert(len_chars, text);
+            old_len_lines
+        };
+        // last line changes, and so does the one before if "\r" got followed by "\n".
+        self.update_rich_content(old_len_lines.saturating_sub(2));
+    }
+
+    /// Returns estimated number of bytes used by all revisions in history.
+    pub fn history_memory_usage(&self) -> usize {
+        self.history.values().map(|revision| revision.memory_estimate).sum()
+    }
+
+    /// Sets memory budget for history (in bytes), compacting it immediately if needed.
+    pub fn set_history_memory_budget(&mut self, budget_op : Option<usize>) {
+        self.history_memory_budget = budget_op;
+        self.compact_history();
+    }
+
+    // Drops or merges revisions, starting from the least useful ones, until history fits in the
+    // budget:
+    // 1) branches that do not lead to current revision, oldest first,
+    // 2) the root, merged into its only child (that is: the oldest undo step is forgotten),
+    // 3) redo branches of current revision, oldest first.
+    // Current revision is never removed, so history can remain above budget.
+    fn compact_history(&mut self) {
+        let budget = match self.history_memory_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+
+        let mut usage = self.history_memory_usage();
+        if usage <= budget {
+            return;
+        }
+
+        let before = (self.history.len(), usage);
+
+        while usage > budget {
+            let compacted = self.remove_oldest_leaf(false) || self.merge_root()
+                || self.remove_oldest_leaf(true);
+            if !compacted {
+                break;
+            }
+            usage = self.history_memory_usage();
+        }
+
+        info!(
+            "compacted history from {} revisions ({} bytes) to {} revisions ({} bytes)",
+            before.0,
+            before.1,
+            self.history.len(),
+            usage
+        );
+    }
+
+    fn is_descendant_of_current(&self, mut id : RevisionId) -> bool {
+        loop {
+            if id == self.current {
+                return true;
+            }
+            match self.history[&id].parent {
+                Some(parent) => id = parent,
+                None => return false,
+            }
+        }
+    }
+
+    // Removes the oldest leaf of the tree (other than current revision), either among redo
+    // branches or among all others. Returns whether anything was removed.
+    fn remove_oldest_leaf(&mut self, redo_branches : bool) -> bool {
+        let leaf_op = self
+            .history
+            .iter()
+            .filter(|&(id, revision)| *id != self.current && revision.children.is_empty())
+            .map(|(id, _)| *id)
+            .filter(|id| self.is_descendant_of_current(*id) == redo_branches)
+            .min();
+
+        let leaf = match leaf_op {
+            Some(leaf) => leaf,
+            None => return false,
+        };
+
+        let removed = self.history.remove(&leaf).unwrap();
+        let parent = self.history.get_mut(&removed.parent.unwrap()).unwrap();
+        parent.children.retain(|child| *child != leaf);
+        if parent.redo_child == Some(leaf) {
+            parent.redo_child = parent.children.last().map(|child| *child);
+        }
+        true
+    }
+
+    // Forgets the root revision, so its child becomes the new root, holding whole text (its change
+    // becomes part of base text, so it no longer counts toward budget). Possible only if root is
+    // not current and has a single child. Returns whether the merge happened.
+    fn merge_root(&mut self) -> bool {
+        if self.root == self.current || self.history[&self.root].children.len() != 1 {
+            return false;
+        }
+
+        let old_root = self.history.remove(&self.root).unwrap();
+        let new_root_id = old_root.children[0];
+        let new_root = self.history.get_mut(&new_root_id).unwrap();
+        new_root.parent = None;
+        new_root.cursors_before = Vec::new();
+        new_root.events = Vec::new();
+        new_root.undo_events = Vec::new();
+        new_root.memory_estimate = 0;
+        self.root = new_root_id;
+        true
     }
 
-    pub fn submit_events(&mut self, events : Vec<EditEvent>) {
-        debug!("got events {:?}", events);
-        let (new_content, num_common_lines) = apply_events(&self.history[self.current], &events);
-        let rope = new_content.lines.clone(); // O(1)
+    /// Moves one step back in history. Returns events that reverted the change, with cursors from
+    /// before it, or None if there was nothing to undo.
+    pub fn undo(&mut self) -> Option<(Vec<EditEvent>, Vec<Cursor>)> {
+        if !self.can_undo() {
+            return None;
+        }
+
+        let undone = self.current;
+        let (parent, num_common_lines, events, cursors) = {
+            let revision = self.current_revision();
+            (
+                revision.parent.unwrap(),
+                revision.num_common_lines,
+                revision.undo_events.clone(),
+                revision.cursors_before.clone(),
+            )
+        };
+
+        self.current = parent;
+        self.current_revision_mut().redo_child = Some(undone);
+
+        self.update_rich_content(num_common_lines);
+        Some((events, cursors))
+    }
+
+    /// Moves one step forward in history. Returns events of the restored change, with cursors from
+    /// after it, or None if there was nothing to redo.
+    pub fn redo(&mut self) -> Option<(Vec<EditEvent>, Vec<Cursor>)> {
+        if !self.can_redo() {
+            return None;
+        }
+
+        self.current = self.current_revision().redo_child.unwrap();
+        let num_common_lines = self.current_revision().num_common_lines;
+        let events = self.current_revision().events.clone();
+        let cursors = self.current_revision().cursors_after.clone();
+
+        self.update_rich_content(num_common_lines);
+        Some((events, cursors))
+    }
+
+    /// Sets content to any revision in history. Redo path is updated to lead from the root to that
+    /// revision. Returns events that led from previous revision to that one (undoing changes up to
+    /// common ancestor of both, then redoing), with cursors from after the revision was made, or
+    /// None if it's not known.
+    pub fn jump_to_revision(&mut self, id : RevisionId) -> Option<(Vec<EditEvent>, Vec<Cursor>)> {
+        if !self.history.contains_key(&id) {
+            return None;
+        }
+
+        let mut path_to_root : Vec<RevisionId> = vec![id];
+        while let Some(parent) = self.history[path_to_root.last().unwrap()].parent {
+            self.history.get_mut(&parent).unwrap().redo_child = Some(*path_to_root.last().unwrap());
+            path_to_root.push(parent);
+        }
 
-        self.history.truncate(self.current + 1); //droping redo's
-        self.history.push(new_content);
-        self.current += 1;
+        let mut events : Vec<EditEvent> = Vec::new();
+        let mut ancestor = self.current;
+        while !path_to_root.contains(&ancestor) {
+            let revision = &self.history[&ancestor];
+            events.extend(revision.undo_events.iter().cloned());
+            ancestor = revision.parent.unwrap();
+        }
+        for revision_id in path_to_root.iter().rev().skip_while(|r| **r != ancestor).skip(1) {
+            events.extend(self.history[revision_id].events.iter().cloned());
+        }
+
+        self.current = id;
+        // Arbitrary jump, no way to tell how many lines are common.
+        self.update_rich_content(0);
+        Some((events, self.current_revision().cursors_after.clone()))
+    }
 
-        // Dropping outdated lines of RichContent. They will be regenerated on-demand.
+    // A batch is merged into current revision only if it's a continuation of the same activity:
+    // both are marked as coalescable, both insert (or both delete), cursors did not move in between
+    // and not much time passed.
+    fn can_coalesce(&self, batch : &EditBatch) -> bool {
+        if !batch.coalesce {
+            return false;
+        }
+
+        // A revision with children is a snapshot other revisions are based on, so it's final.
+        let last = self.current_revision();
+        last.parent.is_some()
+            && last.children.is_empty()
+            && last.coalescable
+            && last.kind == edit_kind(&batch.events)
+            && last.cursors_after == batch.cursors_before
+            && (time::now() - last.timestamp).num_milliseconds() < COALESCE_TIMEOUT_MS
+    }
+
+    // Dropping outdated lines of RichContent. They will be regenerated on-demand.
+    fn update_rich_content(&mut self, num_common_lines : usize) {
+        let rope = self.current_revision().lines.clone(); // O(1)
         self.rich_content.as_mut().map(|rich_content| {
             rich_content.drop_lines(num_common_lines);
             rich_content.update_raw_content(rope);
@@ -186,6 +608,206 @@ impl RopeBasedContentProvider {
     }
 
     pub fn save<T : io::Write>(&self, writer : T) -> io::Result<()> {
-        self.history.last().unwrap().lines.write_to(writer)
+        self.current_revision().lines.write_to(writer)
+    }
+}
+
+// Text removed by a change is kept in events undoing it.
+fn removed_text_estimate(undo_events : &Vec<EditEvent>) -> usize {
+    undo_events
+        .iter()
+        .map(|event| match event {
+            &EditEvent::Insert { ref content, .. } => content.len(),
+            &EditEvent::Change { ref content, .. } => content.len(),
+        })
+        .sum()
+}
+
+fn batch_memory_estimate(batch : &EditBatch) -> usize {
+    let events_estimate : usize = batch
+        .events
+        .iter()
+        .map(|event| {
+            let content_len = match event {
+                &EditEvent::Insert { ref content, .. } => content.len(),
+                &EditEvent::Change { ref content, .. } => content.len(),
+            };
+            content_len + MEMORY_PER_EDIT_ESTIMATE
+        })
+        .sum();
+
+    let cursors_estimate = (batch.cursors_before.len() + batch.cursors_after.len())
+        * std::mem::size_of::<Cursor>();
+
+    events_estimate + cursors_estimate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(text : &str) -> RopeBasedContentProvider {
+        RopeBasedContentProvider::new(Some(&mut text.as_bytes()), None)
+    }
+
+    fn typing(offset : usize, c : &str) -> EditBatch {
+        EditBatch {
+            events :         vec![EditEvent::Insert { offset, content : c.to_string() }],
+            cursors_before : vec![Cursor::new(offset)],
+            cursors_after :  vec![Cursor::new(offset + 1)],
+            coalesce :       true,
+        }
+    }
+
+    #[test]
+    fn undo_redo_restores_text_and_cursors() {
+        let mut p = provider("abc");
+        p.submit_events(EditBatch {
+            events :         vec![EditEvent::Change { offset : 1, length : 1, content : "x".to_string() }],
+            cursors_before : vec![Cursor::new(2)],
+            cursors_after :  vec![Cursor::new(2)],
+            coalesce :       false,
+        });
+        assert_eq!(p.get_lines().to_string(), "axc");
+
+        let undo_events =
+            vec![EditEvent::Change { offset : 1, length : 1, content : "b".to_string() }];
+        assert_eq!(p.undo(), Some((undo_events, vec![Cursor::new(2)])));
+        assert_eq!(p.get_lines().to_string(), "abc");
+        assert_eq!(p.undo(), None);
+
+        let redo_events =
+            vec![EditEvent::Change { offset : 1, length : 1, content : "x".to_string() }];
+        assert_eq!(p.redo(), Some((redo_events, vec![Cursor::new(2)])));
+        assert_eq!(p.get_lines().to_string(), "axc");
+        assert_eq!(p.redo(), None);
+    }
+
+    #[test]
+    fn consecutive_typing_is_one_undo_step() {
+        let mut p = provider("");
+        p.submit_events(typing(0, "a"));
+        p.submit_events(typing(1, "b"));
+        p.submit_events(typing(2, "c"));
+        assert_eq!(p.get_lines().to_string(), "abc");
+
+        let (undo_events, cursors) = p.undo().unwrap();
+        assert_eq!(cursors, vec![Cursor::new(0)]);
+        assert_eq!(p.get_lines().to_string(), "");
+        // the last typed char is reverted first.
+        assert_eq!(undo_events.len(), 3);
+        assert_eq!(
+            undo_events[0],
+            EditEvent::Change { offset : 2, length : 1, content : "".to_string() }
+        );
+
+        let (redo_events, cursors) = p.redo().unwrap();
+        assert_eq!(cursors, vec![Cursor::new(3)]);
+        assert_eq!(redo_events[0], EditEvent::Insert { offset : 0, content : "a".to_string() });
+    }
+
+    #[test]
+    fn deleting_after_typing_is_separate_step() {
+        let mut p = provider("");
+        p.submit_events(typing(0, "a"));
+        p.submit_events(typing(1, "b"));
+        p.submit_events(EditBatch {
+            events :         vec![EditEvent::Change { offset : 1, length : 1, content : "".to_string() }],
+            cursors_before : vec![Cursor::new(2)],
+            cursors_after :  vec![Cursor::new(1)],
+            coalesce :       true,
+        });
+        assert_eq!(p.get_lines().to_string(), "a");
+
+        p.undo();
+        assert_eq!(p.get_lines().to_string(), "ab");
+        p.undo();
+        assert_eq!(p.get_lines().to_string(), "");
+    }
+
+    #[test]
+    fn typing_after_cursor_move_is_separate_step() {
+        let mut p = provider("");
+        p.submit_events(typing(0, "a"));
+        p.submit_events(typing(0, "b"));
+        assert_eq!(p.get_lines().to_string(), "ba");
+
+        p.undo();
+        assert_eq!(p.get_lines().to_string(), "a");
+    }
+
+    #[test]
+    fn edit_after_undo_keeps_old_branch() {
+        let mut p = provider("");
+        p.submit_events(EditBatch::new(vec![EditEvent::Insert { offset : 0, content : "a".to_string() }]));
+        let first_branch = p.current_revision_id();
+
+        p.undo();
+        p.submit_events(EditBatch::new(vec![EditEvent::Insert { offset : 0, content : "b".to_string() }]));
+        assert_eq!(p.get_lines().to_string(), "b");
+        assert_eq!(p.revisions().len(), 3);
+
+        // "b" is undone, then "a" is redone.
+        let (events, _) = p.jump_to_revision(first_branch).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                EditEvent::Change { offset : 0, length : 1, content : "".to_string() },
+                EditEvent::Insert { offset : 0, content : "a".to_string() },
+            ]
+        );
+        assert_eq!(p.get_lines().to_string(), "a");
+
+        // redo path now leads to the revision we jumped to.
+        p.undo();
+        p.redo();
+        assert_eq!(p.get_lines().to_string(), "a");
+    }
+
+    #[test]
+    fn history_is_compacted_to_budget() {
+        let mut p = provider("");
+        for i in 0..10 {
+            p.submit_events(EditBatch::new(vec![EditEvent::Insert { offset : i, content : "x".to_string() }]));
+        }
+        assert_eq!(p.revisions().len(), 11);
+
+        p.set_history_memory_budget(Some(3 * (MEMORY_PER_EDIT_ESTIMATE + 1)));
+        assert!(p.history_memory_usage() <= 3 * (MEMORY_PER_EDIT_ESTIMATE + 1));
+        assert_eq!(p.get_lines().to_string(), "xxxxxxxxxx");
+        assert!(p.can_undo());
+
+        // the oldest revisions are gone, so undo stops early.
+        while p.can_undo() {
+            p.undo();
+        }
+        assert!(p.get_lines().len_chars() > 0);
+    }
+
+    #[test]
+    fn text_bigger_than_budget_keeps_undo() {
+        let budget = 3 * (MEMORY_PER_EDIT_ESTIMATE + 1);
+        let text = "y".repeat(10 * budget);
+        let mut p = provider(&text);
+        p.set_history_memory_budget(Some(budget));
+
+        for content in &["a", "b"] {
+            let event = EditEvent::Insert { offset : 0, content : content.to_string() };
+            p.submit_events(EditBatch::new(vec![event]));
+        }
+        assert!(p.history_memory_usage() <= budget);
+
+        p.undo();
+        assert!(p.undo().is_some());
+        assert_eq!(p.get_lines().to_string(), text);
+    }
+
+    #[test]
+    fn appended_text_is_not_in_history() {
+        let mut p = provider("first\r");
+        p.append_without_history("\nsecond\n");
+        assert_eq!(p.get_lines().to_string(), "first\r\nsecond\n");
+        assert_eq!(p.len_lines(), 3);
+        assert!(!p.can_undo());
     }
 }
@@ -0,0 +1,289 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use content_provider::EditEvent;
+use std::cmp;
+use std::ops::Range;
+
+/// A cursor with an optional selection. All offsets are in CHARS.
+/// Selection spans between anchor and offset, regardless of which one is first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    pub offset :           usize,
+    pub anchor :           Option<usize>,
+    /// Screen column (relative to row of view) the cursor "wants" to be in, remembered while moving
+    /// up and down through shorter lines.
+    pub preferred_column : Option<usize>,
+}
+
+impl Cursor {
+    pub fn new(offset : usize) -> Self {
+        Cursor { offset, anchor : None, preferred_column : None }
+    }
+
+    pub fn with_selection(anchor : usize, offset : usize) -> Self {
+        Cursor { offset, anchor : Some(anchor), preferred_column : None }
+    }
+
+    /// Returns selected range, or None if nothing is selected (also if anchor == offset).
+    pub fn selection(&self) -> Option<Range<usize>> {
+        match self.anchor {
+            Some(anchor) if anchor != self.offset => {
+                Some(cmp::min(anchor, self.offset)..cmp::max(anchor, self.offset))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.selection().is_some()
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.anchor = None;
+    }
+
+    /// Moves cursor to a new offset. If extend is true, selection is started (or extended),
+    /// otherwise it's dropped. Preferred column is not touched.
+    pub fn move_to(&mut self, offset : usize, extend : bool) {
+        if extend {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.offset);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.offset = offset;
+    }
+
+    /// Returns first offset that is covered by cursor or it's selection.
+    pub fn begin(&self) -> usize {
+        self.selection().map(|sel| sel.start).unwrap_or(self.offset)
+    }
+
+    /// Returns last (exclusive) offset covered by cursor or it's selection.
+    pub fn end(&self) -> usize {
+        self.selection().map(|sel| sel.end).unwrap_or(self.offset)
+    }
+
+    /// Moves cursor (and it's anchor) so it points to the same place in text after event was
+    /// applied.
+    pub fn map_through(&mut self, event : &EditEvent) {
+        let (at, removed, inserted) = match event {
+            &EditEvent::Insert { ref offset, ref content } => (*offset, 0, content.chars().count()),
+            &EditEvent::Change { ref offset, ref length, ref content } => {
+                (*offset, *length, content.chars().count())
+            }
+        };
+
+        // a change that begins where cursor's selection ends belongs to text after it (like
+        // the next cursor's selection), so the end stays in front of it.
+        let stays_before = self.begin() < at;
+        let new_offset = map_offset(self.offset, at, removed, inserted, stays_before);
+        if new_offset != self.offset {
+            self.preferred_column = None;
+        }
+        self.offset = new_offset;
+        self.anchor =
+            self.anchor.map(|anchor| map_offset(anchor, at, removed, inserted, stays_before));
+    }
+
+    /// Shortens cursor and it's selection so it fits in a text of len_chars chars.
+    pub fn clamp(&mut self, len_chars : usize) {
+        self.offset = cmp::min(self.offset, len_chars);
+        self.anchor = self.anchor.map(|anchor| cmp::min(anchor, len_chars));
+    }
+}
+
+// Maps offset through replacement of removed chars at "at" with inserted chars. Offsets before
+// the change stay, offsets after it are shifted, and offsets inside the removed part land right
+// after the inserted text. Offset exactly at "at" lands after the inserted text too, unless
+// stays_before is set.
+fn map_offset(
+    offset : usize,
+    at : usize,
+    removed : usize,
+    inserted : usize,
+    stays_before : bool,
+) -> usize {
+    if offset < at || (offset == at && stays_before) {
+        offset
+    } else if offset >= at + removed {
+        offset + inserted - removed
+    } else {
+        at + inserted
+    }
+}
+
+/// Maps all cursors through events (applied in order, as buffer does), then sorts and merges
+/// those that collided.
+pub fn map_cursors(cursors : &mut Vec<Cursor>, events : &Vec<EditEvent>) {
+    for c in cursors.iter_mut() {
+        for event in events {
+            c.map_through(event);
+        }
+    }
+    normalize_cursors(cursors);
+}
+
+// Two cursors collide if their selections overlap, or if one of them lands inside (or on the
+// border of) the other one.
+fn collide(first : &Cursor, second : &Cursor) -> bool {
+    second.begin() < first.end()
+        || first.offset == second.offset
+        || (!second.has_selection() && second.offset <= first.end())
+        || (!first.has_selection() && first.offset >= second.begin())
+}
+
+/// Sorts cursors and merges the colliding ones, so no two cursors overlap. Merged cursor covers
+/// both selections and keeps direction of the first one.
+pub fn normalize_cursors(cursors : &mut Vec<Cursor>) {
+    cursors.sort_by_key(|c| (c.begin(), c.end()));
+
+    let mut result : Vec<Cursor> = Vec::with_capacity(cursors.len());
+    for c in cursors.drain(..) {
+        let merged = match result.last_mut() {
+            Some(last) if collide(last, &c) => {
+                let begin = cmp::min(last.begin(), c.begin());
+                let end = cmp::max(last.end(), c.end());
+                if begin != end {
+                    let backwards = last.has_selection() && last.offset == last.begin();
+                    *last = if backwards {
+                        Cursor::with_selection(end, begin)
+                    } else {
+                        Cursor::with_selection(begin, end)
+                    };
+                }
+                true
+            }
+            _ => false,
+        };
+
+        if !merged {
+            result.push(c);
+        }
+    }
+
+    *cursors = result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_merges_overlapping_cursors() {
+        let mut cursors = vec![
+            Cursor::with_selection(6, 10),
+            Cursor::new(2),
+            Cursor::with_selection(0, 3),
+            Cursor::new(8),
+            Cursor::new(12),
+            Cursor::new(12),
+        ];
+        normalize_cursors(&mut cursors);
+
+        assert_eq!(
+            cursors,
+            vec![Cursor::with_selection(0, 3), Cursor::with_selection(6, 10), Cursor::new(12)]
+        );
+    }
+
+    fn insert(offset : usize, content : &str) -> EditEvent {
+        EditEvent::Insert { offset, content : content.to_string() }
+    }
+
+    fn remove(offset : usize, length : usize) -> EditEvent {
+        EditEvent::Change { offset, length, content : "".to_string() }
+    }
+
+    // Events come in descending order of offsets, the way views produce them.
+    #[test]
+    fn multi_cursor_insert() {
+        let mut cursors = vec![Cursor::new(0), Cursor::new(4), Cursor::new(9)];
+        let events = vec![insert(9, "ab"), insert(4, "ab"), insert(0, "ab")];
+        map_cursors(&mut cursors, &events);
+
+        assert_eq!(cursors, vec![Cursor::new(2), Cursor::new(8), Cursor::new(15)]);
+    }
+
+    #[test]
+    fn multi_cursor_insert_of_multibyte_chars() {
+        let mut cursors = vec![Cursor::new(1), Cursor::new(3)];
+        let events = vec![insert(3, "żółw"), insert(1, "żółw")];
+        map_cursors(&mut cursors, &events);
+
+        assert_eq!(cursors, vec![Cursor::new(5), Cursor::new(11)]);
+    }
+
+    // Backspace at offset 0 produces no event, and must not shift other cursors.
+    #[test]
+    fn multi_cursor_delete_with_one_cursor_at_start() {
+        let mut cursors = vec![Cursor::new(0), Cursor::new(5), Cursor::new(10)];
+        let events = vec![remove(9, 1), remove(4, 1)];
+        map_cursors(&mut cursors, &events);
+
+        assert_eq!(cursors, vec![Cursor::new(0), Cursor::new(4), Cursor::new(8)]);
+    }
+
+    #[test]
+    fn delete_merges_collided_cursors() {
+        let mut cursors = vec![Cursor::new(2), Cursor::new(4), Cursor::new(7)];
+        // removing 1..6 swallows first two cursors
+        let events = vec![remove(1, 6)];
+        map_cursors(&mut cursors, &events);
+
+        assert_eq!(cursors, vec![Cursor::new(1)]);
+    }
+
+    // Paste of different pieces replacing selections of different lengths.
+    #[test]
+    fn multi_cursor_paste_over_selections() {
+        let mut cursors = vec![Cursor::new(3), Cursor::new(10)];
+        let events = vec![
+            EditEvent::Change { offset : 8, length : 2, content : "x".to_string() },
+            EditEvent::Change { offset : 0, length : 3, content : "longer".to_string() },
+        ];
+        map_cursors(&mut cursors, &events);
+
+        assert_eq!(cursors, vec![Cursor::new(6), Cursor::new(12)]);
+    }
+
+    // Typing over adjacent selections (like all occurrences of "ab" in "abab") must not merge
+    // cursors: both end up right after their "x". Cursor of other view between them stays there.
+    #[test]
+    fn typing_over_adjacent_selections() {
+        let mut cursors = vec![Cursor::with_selection(0, 2), Cursor::with_selection(2, 4)];
+        let mut other_view_cursors = vec![Cursor::new(2)];
+        let events = vec![
+            EditEvent::Change { offset : 2, length : 2, content : "x".to_string() },
+            EditEvent::Change { offset : 0, length : 2, content : "x".to_string() },
+        ];
+        map_cursors(&mut cursors, &events);
+        map_cursors(&mut other_view_cursors, &events);
+
+        assert_eq!(cursors.iter().map(|c| c.offset).collect::<Vec<usize>>(), vec![1, 2]);
+        assert!(cursors.iter().all(|c| !c.has_selection()));
+        assert_eq!(other_view_cursors, vec![Cursor::new(2)]);
+    }
+
+    #[test]
+    fn selection_is_mapped_too() {
+        let mut c = Cursor::with_selection(5, 8);
+        c.map_through(&insert(2, "abc"));
+        assert_eq!(c.selection(), Some(8..11));
+    }
+}
@@ -23,35 +23,171 @@ const default_settings_string : &'static str = r####"
   "keybindings" : {
     "text" : {
       "copy" : ["ctrl","c"],
+      "cut" : ["ctrl","x"],
       "paste" : ["ctrl","v"],
+      "paste_from_history" : ["alt","v"],
+      "select_all" : ["ctrl","a"],
+      "find" : ["ctrl","f"],
+      "replace" : ["ctrl","r"],
+      "add_cursor_above" : ["alt","shift","up"],
+      "add_cursor_below" : ["alt","shift","down"],
+      "add_next_occurrence" : ["ctrl","d"],
+      "select_all_occurrences" : ["alt","f3"],
+      "split_selection_into_lines" : ["alt","l"],
+      "word_left" : ["ctrl","left"],
+      "word_right" : ["ctrl","right"],
+      "select_word_left" : ["ctrl","shift","left"],
+      "select_word_right" : ["ctrl","shift","right"],
+      "line_start" : ["home"],
+      "line_end" : ["end"],
+      "select_line_start" : ["shift","home"],
+      "select_line_end" : ["shift","end"],
+      "document_start" : ["ctrl","home"],
+      "document_end" : ["ctrl","end"],
+      "select_document_start" : ["ctrl","shift","home"],
+      "select_document_end" : ["ctrl","shift","end"],
+      "delete_forward" : ["delete"],
+      "delete_word_backward" : ["alt","backspace"],
+      "delete_word_forward" : ["ctrl","delete"],
+      "delete_to_line_end" : ["ctrl","k"],
+      "duplicate" : ["alt","d"],
+      "move_lines_up" : ["alt","up"],
+      "move_lines_down" : ["alt","down"],
+      "join_lines" : ["alt","j"],
+      "delete_lines" : ["alt","k"],
+      "insert_line_below" : ["alt","n"],
+      "insert_line_above" : ["alt","N"],
+      "indent" : ["tab"],
+      "dedent" : ["shift","tab"],
+      "scroll_up" : ["ctrl","up"],
+      "scroll_down" : ["ctrl","down"],
+      "center_on_cursor" : ["ctrl","l"],
       "undo" : ["ctrl","z"],
       "redo" : ["ctrl","Z"]
     },
     "text_view" : {
-      "toggle_syntax_highlighting" : ["ctrl","h"]
+      "toggle_syntax_highlighting" : ["ctrl","h"],
+      "toggle_soft_wrap" : ["alt","z"]
+    },
+    "find_bar" : {
+      "find_next" : ["enter"],
+      "find_previous" : ["shift","f3"],
+      "toggle_case_sensitive" : ["alt","c"],
+      "toggle_whole_word" : ["alt","w"],
+      "toggle_regex" : ["alt","r"],
+      "toggle_in_selection" : ["alt","s"],
+      "switch_field" : ["tab"],
+      "replace_one" : ["alt","enter"],
+      "replace_all" : ["alt","a"],
+      "replace_in_files" : ["alt","p"],
+      "search_results_buffer" : ["alt","b"],
+      "close_find_bar" : ["esc"]
     },
     "global" : {
       "show_file_bar" : ["ctrl", "p"],
       "show_buffer_list" : ["ctrl", "o"],
+      "show_history" : ["alt", "h"],
+      "find_in_files" : ["alt", "f"],
       "command_mode" : ["ctrl", "e"],
       "quit" : ["ctrl", "q"],
       "close_window" : ["esc"],
       "save" : ["ctrl", "s"],
       "save_as" : ["ctrl","w"],
       "open_file_dialog" : ["ctrl", "u"],
-      "start_lsp" : ["ctrl", "g"]
+      "start_lsp" : ["ctrl", "g"],
+      "show_messages" : ["alt", "m"],
+      "show_log" : ["alt", "o"],
+      "split_pane_right" : ["alt", "t"],
+      "split_pane_down" : ["alt", "y"],
+      "close_pane" : ["alt", "q"],
+      "focus_pane_left" : ["ctrl", "alt", "left"],
+      "focus_pane_right" : ["ctrl", "alt", "right"],
+      "focus_pane_up" : ["ctrl", "alt", "up"],
+      "focus_pane_down" : ["ctrl", "alt", "down"],
+      "resize_pane_left" : ["ctrl", "alt", "home"],
+      "resize_pane_right" : ["ctrl", "alt", "end"],
+      "resize_pane_up" : ["ctrl", "alt", "pageup"],
+      "resize_pane_down" : ["ctrl", "alt", "pagedown"]
     },
     "file_bar" : {
     }
   },
+  "editor" : {
+    "indent_string" : "    ",
+    "scroll_off" : 3,
+    "horizontal_scroll_off" : 5,
+    "soft_wrap" : false,
+    "wrap_column" : 0,
+    "tab_width" : 4,
+    "show_whitespace" : "selection",
+    "rulers" : [80]
+  },
+  "whitespace" : {
+    "space" : "\u00b7",
+    "tab" : "\u2192",
+    "trailing" : "\u00b7",
+    "non_breaking_space" : "\u237d",
+    "newline" : "\u21b5",
+    "carriage_return" : "\u240d",
+    "control" : "\ufffd"
+  },
+  "status_bar" : {
+    "left" : ["mode", "modified", "out_of_sync", "file_name"],
+    "right" : ["position", "cursors", "syntax", "line_ending", "lsp"]
+  },
+  "notifications" : {
+    "timeout_ms" : 5000
+  },
+  "mouse" : {
+    "add_cursor_button" : "middle"
+  },
+  "languages" : {
+    "rust" : {
+      "extensions" : ["rs"],
+      "rulers" : [100]
+    },
+    "markdown" : {
+      "extensions" : ["md", "markdown"],
+      "soft_wrap" : true
+    },
+    "text" : {
+      "extensions" : ["txt"],
+      "soft_wrap" : true
+    },
+    "go" : {
+      "extensions" : ["go"],
+      "tab_width" : 8
+    },
+    "makefile" : {
+      "extensions" : ["mk"],
+      "tab_width" : 8,
+      "show_whitespace" : "always"
+    }
+  },
+  "clipboard" : {
+    "backend" : "auto"
+  },
   "performance" : {
-    "auto_highlighting" : true
+    "auto_highlighting" : true,
+    "history_memory_budget_kb" : 65536
   },
   "theme" : {
     "text_view" : {
       "background_color" : "#1d1d1d",
       "primary_text_color" : "#e5e5e5",
-      "secondary_text_color" : "#7f7f7f"
+      "secondary_text_color" : "#7f7f7f",
+      "selection_background_color" : "#264f78",
+      "search_match_background_color" : "#613214",
+      "find_bar_background_color" : "#2e2e2e",
+      "whitespace_color" : "#4b4b4b",
+      "control_char_color" : "#d16969",
+      "trailing_whitespace_background_color" : "#4b1818",
+      "ruler_background_color" : "#262626",
+      "status_bar_background_color" : "#007acc",
+      "status_bar_text_color" : "#ffffff",
+      "info_message_color" : "#ffffff",
+      "warning_message_color" : "#ffd700",
+      "error_message_color" : "#ff5f5f"
     },
     "file_view" :{
       "non_selected_background" : "#282C34",
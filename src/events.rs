@@ -16,6 +16,10 @@ limitations under the License.
 
 use buffer_id::BufferId;
 use content_provider;
+use lsp_client::LspStatus;
+use notifications::Severity;
+use pane_layout::PaneDirection;
+use search::SearchOptions;
 use serde_json as sj;
 use std::path::PathBuf;
 use std::sync::mpsc;
@@ -33,16 +37,31 @@ pub enum IEvent {
     QuitSly,
     ShowFileBar,
     ShowBufferList,
+    ShowHistory,
+    ShowKillRing,
+    ShowMessages,
+    ShowLog,
+    ShowProjectSearch,
+    ShowProjectReplace(String, SearchOptions, String), // query, options, replacement
+    ShowSearchResults(String, SearchOptions),
     ShowSaveAs(BufferId, Option<PathBuf>),
     OpenFileDialog,
     SaveCurrentBuffer,
     FuzzyQueryBarSelected(String, String), /* marker (the word that search ran agains),
                                             * selection (value) */
     CloseWindow,
+    Notify(Severity, String), // message for the user
+    SplitPane(PaneDirection),
+    ClosePane,
+    FocusPane(PaneDirection),
+    ResizePane(PaneDirection),
 
     // Buffer edit events are now in the same queue, not sure yet if that's final.
-    BufferEditEvent(BufferId, Vec<content_provider::EditEvent>),
+    BufferEditEvent(BufferId, ViewHandle, content_provider::EditBatch), // view that made the edit
+    BufferUndo(BufferId),
+    BufferRedo(BufferId),
     EnableLSP,
+    LspStatusChanged(LspStatus),
 
     Proto(String), //for quick hacking.
 }
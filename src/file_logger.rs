@@ -0,0 +1,172 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Logger writing to a file, as stderr is taken by the interface. By default the file is sly.log in
+// data directory. Once it grows over MAX_FILE_SIZE, it's renamed to "sly.log.1" (older ones being
+// shifted up to NUM_ROTATED) and a new one is started.
+
+use log;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use time;
+
+const MAX_FILE_SIZE : u64 = 4 * 1024 * 1024;
+const NUM_ROTATED : usize = 3;
+const FILE_NAME : &'static str = "sly.log";
+const CRATE_NAME : &'static str = env!("CARGO_PKG_NAME");
+
+struct LogFile {
+    file : File,
+    size : u64,
+}
+
+pub struct FileLogger {
+    path :     PathBuf,
+    level :    LevelFilter,
+    max_size : u64,
+    file :     Mutex<LogFile>,
+}
+
+/// Returns directory for application data: $XDG_DATA_HOME/sly, or ~/.local/share/sly.
+pub fn data_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir).join(CRATE_NAME)),
+        _ => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local").join("share").join(CRATE_NAME)),
+    }
+}
+
+pub fn default_log_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(FILE_NAME))
+}
+
+fn rotated_path(path : &Path, idx : usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", idx));
+    path.with_file_name(name)
+}
+
+// Shifts "<name>.1" to "<name>.2" and so on, dropping the oldest one, then moves file to
+// "<name>.1".
+fn rotate(path : &Path) -> io::Result<()> {
+    for idx in (1..NUM_ROTATED).rev() {
+        let from = rotated_path(path, idx);
+        if from.exists() {
+            fs::rename(&from, rotated_path(path, idx + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))
+}
+
+fn open_for_append(path : &Path) -> io::Result<LogFile> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(LogFile { file : file, size : size })
+}
+
+impl FileLogger {
+    fn new(path : &Path, level : LevelFilter, max_size : u64) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut log_file = open_for_append(path)?;
+        if log_file.size > max_size {
+            rotate(path)?;
+            log_file = open_for_append(path)?;
+        }
+
+        Ok(FileLogger {
+            path :     path.to_owned(),
+            level :    level,
+            max_size : max_size,
+            file :     Mutex::new(log_file),
+        })
+    }
+
+    fn write_line(&self, line : &str) -> io::Result<()> {
+        let mut log_file = self.file.lock().unwrap();
+        if log_file.size > 0 && log_file.size + line.len() as u64 > self.max_size {
+            rotate(&self.path)?;
+            *log_file = open_for_append(&self.path)?;
+        }
+
+        log_file.file.write_all(line.as_bytes())?;
+        log_file.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata : &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with(CRATE_NAME)
+    }
+
+    fn log(&self, record : &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time_str = time::strftime("%Y-%m-%d %H:%M:%S", &time::now()).unwrap_or_default();
+        let line =
+            format!("{} {:<5} {}: {}\n", time_str, record.level(), record.target(), record.args());
+        // There is nowhere to report failure of logger itself.
+        let _ = self.write_line(&line);
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().file.flush();
+    }
+}
+
+/// Installs logger writing messages of given level and more severe ones to file at path.
+pub fn init(path : &Path, level : LevelFilter) -> io::Result<()> {
+    let logger = FileLogger::new(path, level, MAX_FILE_SIZE)?;
+    log::set_boxed_logger(Box::new(logger))
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn full_file_is_rotated() {
+        let dir = env::temp_dir().join(format!("sly-file-logger-test-{}", process::id()));
+        let path = dir.join(FILE_NAME);
+        let _ = fs::remove_dir_all(&dir);
+        let logger = FileLogger::new(&path, LevelFilter::Info, 10).unwrap();
+
+        for line in &["first\n", "second\n", "third\n"] {
+            logger.write_line(line).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "second\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 2)).unwrap(), "first\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
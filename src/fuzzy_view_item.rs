@@ -33,7 +33,11 @@ use unicode_segmentation::UnicodeSegmentation as us;
 use std::collections::HashMap;
 use std::marker::Sized;
 
+use content_provider::RevisionInfo;
+use kill_ring::{join_pieces, KillRing};
+use notifications::Notifications;
 use std::cmp::{Eq, PartialEq};
+use time;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -105,3 +109,74 @@ pub fn file_list_to_items(file_list : &Vec<PathBuf>) -> Vec<ViewItem> {
         })
         .collect()
 }
+
+/// Newest revisions come first, as these are the ones that are looked for most often.
+/// Current revision is marked with memory used by the whole history (in bytes).
+pub fn revisions_to_items(revisions : &Vec<RevisionInfo>, memory_usage : usize) -> Vec<ViewItem> {
+    revisions
+        .iter()
+        .rev()
+        .map(|r| {
+            let time_str = time::strftime("%H:%M:%S", &r.timestamp).unwrap_or_default();
+            let header = if r.is_current {
+                let usage_kb = memory_usage / 1024;
+                format!("#{} {} (current, history uses ~{} kB)", r.id, time_str, usage_kb)
+            } else {
+                format!("#{} {}", r.id, time_str)
+            };
+
+            let desc = match r.parent {
+                Some(parent) => format!(
+                    "{} lines, based on #{}, {} branch(es), ~{} kB",
+                    r.num_lines,
+                    parent,
+                    r.num_children,
+                    r.memory_estimate / 1024
+                ),
+                None => format!("{} lines, oldest kept revision", r.num_lines),
+            };
+
+            ViewItem { header : header, desc : Some(desc), marker : r.id.to_string() }
+        })
+        .collect()
+}
+
+const KILL_RING_HEADER_LEN : usize = 60;
+
+/// Marker is the position of entry on the kill ring, most recent first.
+pub fn kill_ring_to_items(kill_ring : &KillRing) -> Vec<ViewItem> {
+    kill_ring
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(idx, pieces)| {
+            let text = join_pieces(pieces);
+            let first_line = text.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+            let header : String = first_line.trim().chars().take(KILL_RING_HEADER_LEN).collect();
+
+            let desc = format!(
+                "{} piece(s), {} line(s), {} chars",
+                pieces.len(),
+                text.lines().count(),
+                text.chars().count()
+            );
+
+            ViewItem { header : header, desc : Some(desc), marker : idx.to_string() }
+        })
+        .collect()
+}
+
+/// Newest messages come first. Marker is the position of message in history, oldest first.
+pub fn notifications_to_items(notifications : &Notifications) -> Vec<ViewItem> {
+    notifications
+        .history()
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(idx, n)| {
+            let time_str = time::strftime("%H:%M:%S", &n.timestamp).unwrap_or_default();
+            let desc = format!("{} at {}", n.severity.name(), time_str);
+            ViewItem { header : n.text.clone(), desc : Some(desc), marker : idx.to_string() }
+        })
+        .collect()
+}
@@ -39,9 +39,19 @@ use buffer_id::BufferId;
 use core::borrow::BorrowMut;
 use events::IChannel;
 use file_dialog::FileDialog;
+use content_provider::{EditBatch, EditEvent, RevisionId};
+use cursor::{map_cursors, Cursor};
 use fuzzy_query_view::FuzzyQueryResult;
-use lsp_client::LspClient;
+use fuzzy_view_item::{kill_ring_to_items, notifications_to_items, revisions_to_items, ViewItem};
+use lsp_client::{LspClient, LspStatus};
+use notifications::Severity;
 use overlay_dialog::OverlayDialog;
+use pane_layout::PaneDirection;
+use pane_view::PaneView;
+use project_replace_view::{ProjectReplaceResult, ProjectReplaceView};
+use project_search;
+use search::SearchOptions;
+use simple_fuzzy_index::SimpleIndex;
 use sly_view::SlyView;
 use std::any::Any;
 use std::cell::RefCell;
@@ -57,12 +67,23 @@ use std::path::PathBuf;
 use std::rc::{Rc, Weak};
 use std::sync::mpsc;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use view_handle::ViewHandle;
 use std::cell::Ref;
 
 const FILE_BAR_MARKER : &'static str = "file_bar";
 const BUFFER_LIST_MARKER : &'static str = "file_bar";
+const HISTORY_LIST_MARKER : &'static str = "history_list";
+const KILL_RING_MARKER : &'static str = "kill_ring";
+const PROJECT_SEARCH_MARKER : &'static str = "project_search";
+const MESSAGES_MARKER : &'static str = "messages";
+
+// Refresh rate used while screen changes without input: a message is shown (so it disappears when
+// it expires) or a read-only buffer follows it's file.
+const REFRESH_FPS : u32 = 2;
+
+// Percent of split moved by a single resize of pane.
+const PANE_RESIZE_STEP : usize = 5;
 
 /*
 At this moment I have not decided on whether interface holds premise before siv or other way around.
@@ -71,17 +92,34 @@ and other way around.
 */
 
 pub struct Interface {
-    state :                AppState,
-    channel :              (mpsc::Sender<IEvent>, mpsc::Receiver<IEvent>),
-    siv :                  Cursive,
-    active_editor_handle : ViewHandle,
-    inactive_editors :     HashMap<BufferId, IdView<SlyTextView>>,
-    path_to_buffer_id :    HashMap<PathBuf, BufferId>,
-    done :                 bool,
-    file_dialog_handle :   Option<ViewHandle>,
-    file_bar_handle :      Option<ViewHandle>,
-    buffer_list_handle :   Option<ViewHandle>,
-    lsp_clients :          Vec<LspClient>, //TODO(njskalski): temporary storage to avoid removal
+    state :                  AppState,
+    channel :                (mpsc::Sender<IEvent>, mpsc::Receiver<IEvent>),
+    siv :                    Cursive,
+    panes_handle :           ViewHandle,
+    inactive_editors :       HashMap<BufferId, IdView<SlyTextView>>,
+    path_to_buffer_id :      HashMap<PathBuf, BufferId>,
+    done :                   bool,
+    file_dialog_handle :     Option<ViewHandle>,
+    file_bar_handle :        Option<ViewHandle>,
+    buffer_list_handle :     Option<ViewHandle>,
+    history_list_handle :    Option<ViewHandle>,
+    kill_ring_handle :       Option<ViewHandle>,
+    project_search_handle :  Option<ViewHandle>,
+    project_replace_handle : Option<ViewHandle>,
+    messages_handle :        Option<ViewHandle>,
+    lsp_clients :            Vec<LspClient>, //TODO(njskalski): temporary storage to avoid removal
+}
+
+fn pane_direction(action : &str) -> PaneDirection {
+    if action.ends_with("left") {
+        PaneDirection::Left
+    } else if action.ends_with("right") {
+        PaneDirection::Right
+    } else if action.ends_with("up") {
+        PaneDirection::Up
+    } else {
+        PaneDirection::Down
+    }
 }
 
 fn find_view_with_handle<V>(
@@ -123,23 +161,37 @@ impl Interface {
         siv.set_theme(theme);
 
         let buffer_observer = state.get_first_buffer().unwrap(); // TODO(njskalski): panics. Semantics unclear.
-        let sly_text_view = SlyTextView::new(state.settings_rc().clone(), buffer_observer, channel.0.clone());
-        let active_editor = sly_text_view.handle().clone();
+        let sly_text_view = SlyTextView::new(
+            state.settings_rc().clone(),
+            state.kill_ring_rc().clone(),
+            state.clipboard_rc().clone(),
+            state.lsp_status_rc().clone(),
+            state.notifications_rc().clone(),
+            buffer_observer,
+            channel.0.clone(),
+        );
+        let pane_view = PaneView::new(sly_text_view, state.settings_rc().clone());
+        let panes_handle = pane_view.handle();
 
-        siv.add_fullscreen_layer(sly_text_view);
+        siv.add_fullscreen_layer(pane_view);
 
         let mut i = Interface {
-            state :                state,
-            channel :              channel,
-            siv :                  siv,
-            active_editor_handle : active_editor,
-            inactive_editors :     HashMap::new(),
-            path_to_buffer_id :    HashMap::new(),
-            done :                 false,
-            file_dialog_handle :   None,
-            file_bar_handle :      None,
-            buffer_list_handle :   None,
-            lsp_clients :          Vec::new(),
+            state :                  state,
+            channel :                channel,
+            siv :                    siv,
+            panes_handle :           panes_handle,
+            inactive_editors :       HashMap::new(),
+            path_to_buffer_id :      HashMap::new(),
+            done :                   false,
+            file_dialog_handle :     None,
+            file_bar_handle :        None,
+            buffer_list_handle :     None,
+            history_list_handle :    None,
+            kill_ring_handle :       None,
+            project_search_handle :  None,
+            project_replace_handle : None,
+            messages_handle :        None,
+            lsp_clients :            Vec::new(),
         };
 
         // let known_actions = vec!["show_everything_bar"];
@@ -163,6 +215,16 @@ impl Interface {
                         ch.send(IEvent::ShowBufferList).unwrap();
                     });
                 }
+                "show_history" => {
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::ShowHistory).unwrap();
+                    });
+                }
+                "find_in_files" => {
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::ShowProjectSearch).unwrap();
+                    });
+                }
                 "save" => {
                     i.siv.add_global_callback(event, move |_| {
                         ch.send(IEvent::SaveCurrentBuffer).unwrap();
@@ -183,6 +245,44 @@ impl Interface {
                         ch.send(IEvent::EnableLSP).unwrap();
                     });
                 }
+                "show_messages" => {
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::ShowMessages).unwrap();
+                    });
+                }
+                "show_log" => {
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::ShowLog).unwrap();
+                    });
+                }
+                "split_pane_right" => {
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::SplitPane(PaneDirection::Right)).unwrap();
+                    });
+                }
+                "split_pane_down" => {
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::SplitPane(PaneDirection::Down)).unwrap();
+                    });
+                }
+                "close_pane" => {
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::ClosePane).unwrap();
+                    });
+                }
+                "focus_pane_left" | "focus_pane_right" | "focus_pane_up" | "focus_pane_down" => {
+                    let direction = pane_direction(&action);
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::FocusPane(direction)).unwrap();
+                    });
+                }
+                "resize_pane_left" | "resize_pane_right" | "resize_pane_up"
+                | "resize_pane_down" => {
+                    let direction = pane_direction(&action);
+                    i.siv.add_global_callback(event, move |_| {
+                        ch.send(IEvent::ResizePane(direction)).unwrap();
+                    });
+                }
                 _ => {
                     debug!("unknown action {:?} bound with event global {:?}", action, event);
                 }
@@ -192,21 +292,11 @@ impl Interface {
         i
     }
 
-    // TODO(njskalski): error handling, borrow instead of copy etc.
     fn replace_current_editor_view(
         &mut self,
         new_editor : IdView<SlyTextView>,
     ) -> IdView<SlyTextView> {
-        // removing old view
-        let handle = self.active_editor_handle.clone();
-        let old_view = self.remove_window::<SlyTextView>(&handle);
-
-        // inserting new view
-        self.active_editor_handle = new_editor.handle();
-        self.siv.screen_mut().add_layer(new_editor);
-
-        // returning old view
-        old_view.unwrap()
+        self.pane_view().replace_focused(new_editor)
     }
 
     fn remove_window<T>(&mut self, handle : &ViewHandle) -> Option<IdView<T>>
@@ -224,21 +314,17 @@ impl Interface {
     }
 
     // TODO(njskalski): add proper handling of errrors, it's a total mess now!
-    fn create_editor_for_buffer_id(&mut self, buffer_id : &BufferId) -> Result<(), InterfaceError> {
-        {
-            let old_editor = self.inactive_editors.get(buffer_id);
-            if old_editor.is_some() {
-                panic!("attempt to re-create editor for buffer_id {}", buffer_id);
-            }
-        }
-
+    fn create_editor_for_buffer_id(&mut self, buffer_id : &BufferId) -> IdView<SlyTextView> {
         let obs = self.state.buffer_obs(buffer_id).unwrap(); //TODO panics
-        let mut view = SlyTextView::new(self.settings_rc().clone(), obs, self.event_sink());
-        if self.inactive_editors.insert(buffer_id.clone(), view).is_some() {
-            panic!("insertion failed, object already present");
-        }
-
-        Ok(())
+        SlyTextView::new(
+            self.settings_rc().clone(),
+            self.state.kill_ring_rc().clone(),
+            self.state.clipboard_rc().clone(),
+            self.state.lsp_status_rc().clone(),
+            self.state.notifications_rc().clone(),
+            obs,
+            self.event_sink(),
+        )
     }
 
     fn process_events(&mut self) {
@@ -254,9 +340,14 @@ impl Interface {
                 IEvent::CloseWindow => {
                     self.cancel_floating_windows();
                 }
-                IEvent::BufferEditEvent(view_handle, events) => {
-                    //TODO now I just send to active editor, ignoring view_handle
-                    self.active_editor().buffer_obs().submit_edit_events_to_buffer(events);
+                IEvent::BufferEditEvent(buffer_id, view_handle, batch) => {
+                    self.submit_edit(&buffer_id, batch, Some(&view_handle));
+                }
+                IEvent::BufferUndo(_) => {
+                    self.move_in_history(|editor| editor.undo());
+                }
+                IEvent::BufferRedo(_) => {
+                    self.move_in_history(|editor| editor.redo());
                 }
                 IEvent::SaveCurrentBuffer => {
                     self.save_current_buffer();
@@ -267,9 +358,48 @@ impl Interface {
                 IEvent::ShowBufferList => {
                     self.show_buffer_list();
                 }
+                IEvent::ShowHistory => {
+                    self.show_history();
+                }
+                IEvent::ShowKillRing => {
+                    self.show_kill_ring();
+                }
+                IEvent::ShowMessages => {
+                    self.show_messages();
+                }
+                IEvent::ShowLog => {
+                    self.show_log();
+                }
+                IEvent::Notify(severity, text) => {
+                    self.notify(severity, text);
+                }
+                IEvent::SplitPane(direction) => {
+                    self.split_pane(direction);
+                }
+                IEvent::ClosePane => {
+                    self.close_pane();
+                }
+                IEvent::FocusPane(direction) => {
+                    self.pane_view().focus(direction);
+                }
+                IEvent::ResizePane(direction) => {
+                    self.pane_view().resize(direction, PANE_RESIZE_STEP);
+                }
+                IEvent::ShowProjectSearch => {
+                    self.show_project_search();
+                }
+                IEvent::ShowProjectReplace(query, options, replacement) => {
+                    self.show_project_replace(&query, &options, &replacement);
+                }
+                IEvent::ShowSearchResults(query, options) => {
+                    self.show_search_results(&query, &options);
+                }
                 IEvent::EnableLSP => {
                     self.enable_lsp();
                 }
+                IEvent::LspStatusChanged(status) => {
+                    self.state.lsp_status_rc().set(status);
+                }
                 _ => {
                     debug!("unhandled IEvent {:?}", &msg);
                 }
@@ -290,9 +420,12 @@ impl Interface {
                 match result {
                     Ok(FileDialogResult::Cancel) => {}
                     Ok(FileDialogResult::FileSave(buffer_id, path)) => {
-                        match self.state.save_buffer_as(&buffer_id, path) {
-                            Ok(()) => {}
-                            Err(e) => error!("file save failed, because \"{}\"", e),
+                        match self.state.save_buffer_as(&buffer_id, path.clone()) {
+                            Ok(()) => self.notify(Severity::Info, format!("saved {:?}", path)),
+                            Err(e) => self.notify(
+                                Severity::Error,
+                                format!("file save failed, because \"{}\"", e),
+                            ),
                         }
                     }
                     Ok(FileDialogResult::FileOpen(path)) => {
@@ -300,7 +433,10 @@ impl Interface {
                         debug!("buffer_id {:?}", buf_id);
                     }
                     Err(e) => {
-                        error!("opening file failed, because \"{}\"", e);
+                        self.notify(
+                            Severity::Error,
+                            format!("opening file failed, because \"{}\"", e),
+                        );
                     }
                 }
 
@@ -320,7 +456,10 @@ impl Interface {
                         self.open_and_or_focus_file(item_marker);
                     }
                     Err(e) => {
-                        error!("opening file failed, because \"{}\"", e);
+                        self.notify(
+                            Severity::Error,
+                            format!("opening file failed, because \"{}\"", e),
+                        );
                     }
                 }
                 let handle = self.file_bar_handle.take().unwrap();
@@ -339,7 +478,10 @@ impl Interface {
                         self.open_and_or_focus(&BufferId::from_string(&buffer_id_str).unwrap());
                     }
                     Err(e) => {
-                        error!("opening buffer failed, because \"{}\"", e);
+                        self.notify(
+                            Severity::Error,
+                            format!("opening buffer failed, because \"{}\"", e),
+                        );
                     }
                 }
                 let handle = self.buffer_list_handle.take().unwrap();
@@ -347,16 +489,145 @@ impl Interface {
             }
         }
 
+        if self.history_list_handle.is_some() {
+            let mut history_list = self.history_list().unwrap();
+
+            if let Some(result) = history_list.get_result() {
+                match result {
+                    Ok(FuzzyQueryResult::Cancel) => {}
+                    Ok(FuzzyQueryResult::Selected(_, revision_str)) => {
+                        debug!("selected revision {}", &revision_str);
+                        match revision_str.parse::<RevisionId>() {
+                            Ok(revision_id) => {
+                                self.move_in_history(|editor| editor.jump_to_revision(revision_id))
+                            }
+                            Err(e) => error!("unable to parse revision id, because \"{}\"", e),
+                        }
+                    }
+                    Err(e) => {
+                        self.notify(
+                            Severity::Error,
+                            format!("selecting revision failed, because \"{}\"", e),
+                        );
+                    }
+                }
+                let handle = self.history_list_handle.take().unwrap();
+                self.remove_window::<FuzzyQueryView>(&handle);
+            }
+        }
+
+        if self.kill_ring_handle.is_some() {
+            let mut kill_ring_list = self.kill_ring_list().unwrap();
+
+            if let Some(result) = kill_ring_list.get_result() {
+                match result {
+                    Ok(FuzzyQueryResult::Cancel) => {}
+                    Ok(FuzzyQueryResult::Selected(_, idx_str)) => {
+                        debug!("selected kill ring entry {}", &idx_str);
+                        match idx_str.parse::<usize>() {
+                            Ok(idx) => self.paste_from_kill_ring(idx),
+                            Err(e) => {
+                                error!("unable to parse kill ring index, because \"{}\"", e)
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        self.notify(
+                            Severity::Error,
+                            format!("selecting kill ring entry failed, because \"{}\"", e),
+                        );
+                    }
+                }
+                let handle = self.kill_ring_handle.take().unwrap();
+                self.remove_window::<FuzzyQueryView>(&handle);
+            }
+        }
+
+        if self.project_search_handle.is_some() {
+            let mut project_search = self.project_search().unwrap();
+
+            if let Some(result) = project_search.get_result() {
+                match result {
+                    Ok(FuzzyQueryResult::Cancel) => {}
+                    Ok(FuzzyQueryResult::Selected(_, marker)) => {
+                        debug!("selected project search match {}", &marker);
+                        match project_search::parse_marker(&marker) {
+                            Some((path, line, column)) => {
+                                self.open_and_or_focus_file(path);
+                                self.active_editor().go_to(line, column);
+                            }
+                            None => error!("unable to parse project search marker {}", marker),
+                        }
+                    }
+                    Err(e) => {
+                        self.notify(
+                            Severity::Error,
+                            format!("selecting project search match failed, because \"{}\"", e),
+                        );
+                    }
+                }
+                let handle = self.project_search_handle.take().unwrap();
+                self.remove_window::<FuzzyQueryView>(&handle);
+            }
+        }
+
+        if self.project_replace_handle.is_some() {
+            let mut project_replace = self.project_replace().unwrap();
+
+            if let Some(result) = project_replace.get_result() {
+                match result {
+                    Ok(ProjectReplaceResult::Cancel) => {}
+                    Ok(ProjectReplaceResult::Apply(files)) => {
+                        let edits = self.state.replacement_edits(files);
+                        let text = format!("project replace changed {} buffer(s)", edits.len());
+                        for (buffer_id, events) in edits {
+                            self.apply_edit_events(&buffer_id, events);
+                        }
+                        self.notify(Severity::Info, text);
+                    }
+                    Err(e) => {
+                        self.notify(
+                            Severity::Error,
+                            format!("project replace failed, because \"{}\"", e),
+                        );
+                    }
+                }
+                let handle = self.project_replace_handle.take().unwrap();
+                self.remove_window::<ProjectReplaceView>(&handle);
+            }
+        }
+
+        if self.messages_handle.is_some() {
+            let mut messages = self.messages().unwrap();
+
+            if let Some(result) = messages.get_result() {
+                match result {
+                    Ok(FuzzyQueryResult::Cancel) => {}
+                    Ok(FuzzyQueryResult::Selected(_, idx_str)) => {
+                        debug!("selected message {}", &idx_str);
+                    }
+                    Err(e) => {
+                        error!("browsing messages failed, because \"{}\"", e);
+                    }
+                }
+                let handle = self.messages_handle.take().unwrap();
+                self.remove_window::<FuzzyQueryView>(&handle);
+            }
+        }
+
         // TODO(njskalski): add processing of file_bar and fuzzy stuff.
     }
 
     /// This updates interface and SIV!
     fn open_and_or_focus(&mut self, buffer_id : &BufferId) {
-        if !self.inactive_editors.contains_key(buffer_id) {
-            self.create_editor_for_buffer_id(buffer_id);
+        if self.active_editor().buffer_obs().buffer_id() == *buffer_id {
+            return;
         }
 
-        let new_editor = self.inactive_editors.remove(buffer_id).unwrap();
+        let new_editor = match self.inactive_editors.remove(buffer_id) {
+            Some(editor) => editor,
+            None => self.create_editor_for_buffer_id(buffer_id),
+        };
         let mut old_editor = self.replace_current_editor_view(new_editor);
         let old_editor_buffer_id = old_editor.get_mut().buffer_obs().buffer_id().clone();
         self.inactive_editors.insert(old_editor_buffer_id, old_editor);
@@ -383,12 +654,155 @@ impl Interface {
         self.open_and_or_focus(&buffer_id);
     }
 
+    fn pane_view(&mut self) -> ViewRef<PaneView> {
+        self.siv.find_id(&self.panes_handle.to_string()).unwrap()
+    }
+
+    /// Returns editor in focused pane.
     fn active_editor(&mut self) -> ViewRef<SlyTextView> {
-        let editor = self.siv.find_id(&self.active_editor_handle.to_string()).unwrap()
-            as views::ViewRef<SlyTextView>;
+        // PaneView has to be released before looking for views inside of it.
+        let handle = self.pane_view().focused().clone();
+        let editor =
+            self.siv.find_id(&handle.to_string()).unwrap() as views::ViewRef<SlyTextView>;
         editor
     }
 
+    /// Opens buffer of focused pane in a new one, next to it in given direction. The new pane
+    /// starts with the same cursors and scroll position, but has them separate from now on.
+    fn split_pane(&mut self, direction : PaneDirection) {
+        let buffer_id = self.active_editor().buffer_obs().buffer_id();
+        let mut new_editor = self.create_editor_for_buffer_id(&buffer_id);
+        {
+            let active_editor = self.active_editor();
+            new_editor.get_mut().copy_position_from(&active_editor);
+        }
+        self.pane_view().split(new_editor, direction);
+    }
+
+    /// Applies batch to buffer and maps cursors of all it's views through it, except for the view
+    /// that made the edit (it has done that already).
+    fn submit_edit(
+        &mut self,
+        buffer_id : &BufferId,
+        batch : EditBatch,
+        origin_op : Option<&ViewHandle>,
+    ) {
+        let obs = match self.state.buffer_obs(buffer_id) {
+            Some(obs) => obs,
+            None => {
+                error!("edit of unknown buffer {}", buffer_id);
+                return;
+            }
+        };
+
+        self.map_cursors_of_views(buffer_id, &batch.events, origin_op);
+        obs.submit_edit_events_to_buffer(batch);
+    }
+
+    /// Applies events that did not come from a view. Cursors of a view showing the buffer are
+    /// stored along, so undo and redo in that view keep them in place.
+    fn apply_edit_events(&mut self, buffer_id : &BufferId, events : Vec<EditEvent>) {
+        let cursors_before = self.view_cursors(buffer_id).unwrap_or_default();
+        let mut cursors_after = cursors_before.clone();
+        map_cursors(&mut cursors_after, &events);
+
+        let batch = EditBatch {
+            events :         events,
+            cursors_before : cursors_before,
+            cursors_after :  cursors_after,
+            coalesce :       false,
+        };
+        self.submit_edit(buffer_id, batch, None);
+    }
+
+    // Returns cursors of a view of buffer, preferring the focused one.
+    fn view_cursors(&mut self, buffer_id : &BufferId) -> Option<Vec<Cursor>> {
+        {
+            let editor = self.active_editor();
+            if editor.buffer_obs().buffer_id() == *buffer_id {
+                return Some(editor.cursors().clone());
+            }
+        }
+
+        let mut result : Option<Vec<Cursor>> = None;
+        {
+            let mut take = |editor : &mut SlyTextView| {
+                if result.is_none() && editor.buffer_obs().buffer_id() == *buffer_id {
+                    result = Some(editor.cursors().clone());
+                }
+            };
+            self.pane_view().for_each_editor(&mut take);
+            for editor in self.inactive_editors.values_mut() {
+                editor.with_view_mut(|editor| take(editor));
+            }
+        }
+        result
+    }
+
+    // Covers both views shown in panes and the inactive ones.
+    fn map_cursors_of_views(
+        &mut self,
+        buffer_id : &BufferId,
+        events : &Vec<EditEvent>,
+        except_op : Option<&ViewHandle>,
+    ) {
+        let mut map = |editor : &mut SlyTextView| {
+            if editor.buffer_obs().buffer_id() == *buffer_id
+                && except_op != Some(&editor.handle())
+            {
+                editor.map_cursors_through(events);
+            }
+        };
+
+        self.pane_view().for_each_editor(&mut map);
+        for editor in self.inactive_editors.values_mut() {
+            editor.with_view_mut(|editor| map(editor));
+        }
+    }
+
+    // Undoes, redoes or jumps in history of buffer of focused pane, then maps cursors of other
+    // views of that buffer through events that were applied.
+    fn move_in_history<F : FnOnce(&mut SlyTextView) -> Vec<EditEvent>>(&mut self, f : F) {
+        let (buffer_id, handle, events) = {
+            let mut editor = self.active_editor();
+            let events = f(&mut *editor);
+            (editor.buffer_obs().buffer_id(), editor.handle(), events)
+        };
+        self.map_cursors_of_views(&buffer_id, &events, Some(&handle));
+    }
+
+    /// Reloads read-only buffers shown in panes (like the log), and moves views that were at the
+    /// end of such buffer to it's new end. Returns whether any such buffer is shown.
+    fn follow_read_only_buffers(&mut self) -> bool {
+        let mut shown : Vec<BufferId> = Vec::new();
+        self.pane_view().for_each_editor(|editor| {
+            let buffer_id = editor.buffer_obs().buffer_id();
+            if editor.buffer_obs().is_read_only() && !shown.contains(&buffer_id) {
+                shown.push(buffer_id);
+            }
+        });
+
+        for (buffer_id, old_len_chars) in self.state.reload_read_only_buffers(&shown) {
+            self.pane_view().for_each_editor(|editor| {
+                if editor.buffer_obs().buffer_id() == buffer_id {
+                    editor.follow_end(old_len_chars);
+                }
+            });
+        }
+        !shown.is_empty()
+    }
+
+    fn close_pane(&mut self) {
+        let closed_op = self.pane_view().close_focused();
+        match closed_op {
+            Some(mut editor) => {
+                let buffer_id = editor.get_mut().buffer_obs().buffer_id();
+                self.inactive_editors.entry(buffer_id).or_insert(editor);
+            }
+            None => self.notify(Severity::Info, "can't close the last pane".to_string()),
+        }
+    }
+
     fn focus_buffer(&mut self, buffer_id : BufferId) {}
 
     fn file_dialog(&mut self) -> Option<ViewRef<FileDialog>> {
@@ -403,6 +817,26 @@ impl Interface {
         find_view_with_handle(&mut self.siv, &self.buffer_list_handle)
     }
 
+    fn history_list(&mut self) -> Option<ViewRef<FuzzyQueryView>> {
+        find_view_with_handle(&mut self.siv, &self.history_list_handle)
+    }
+
+    fn kill_ring_list(&mut self) -> Option<ViewRef<FuzzyQueryView>> {
+        find_view_with_handle(&mut self.siv, &self.kill_ring_handle)
+    }
+
+    fn project_search(&mut self) -> Option<ViewRef<FuzzyQueryView>> {
+        find_view_with_handle(&mut self.siv, &self.project_search_handle)
+    }
+
+    fn project_replace(&mut self) -> Option<ViewRef<ProjectReplaceView>> {
+        find_view_with_handle(&mut self.siv, &self.project_replace_handle)
+    }
+
+    fn messages(&mut self) -> Option<ViewRef<FuzzyQueryView>> {
+        find_view_with_handle(&mut self.siv, &self.messages_handle)
+    }
+
     fn cancel_floating_windows(&mut self) {
         self.file_dialog().map(|mut file_dialog_ref| file_dialog_ref.borrow_mut().cancel());
     }
@@ -415,6 +849,16 @@ impl Interface {
 
             self.process_events();
 
+            self.state.check_buffers_sync();
+            let following = self.follow_read_only_buffers();
+
+            // Without input there would be no redraw to hide an expired message or show new lines
+            // of log.
+            let message_shown =
+                self.state.notifications_rc().borrow().current(Instant::now()).is_some();
+            let refresh = message_shown || following;
+            self.siv.set_fps(if refresh { REFRESH_FPS } else { 0 });
+
             if !self.done {
                 self.siv.step();
             }
@@ -425,6 +869,11 @@ impl Interface {
         (if self.file_dialog_handle.is_some() { 1 } else { 0 })
             + (if self.buffer_list_handle.is_some() { 1 } else { 0 })
             + (if self.file_bar_handle.is_some() { 1 } else { 0 })
+            + (if self.history_list_handle.is_some() { 1 } else { 0 })
+            + (if self.kill_ring_handle.is_some() { 1 } else { 0 })
+            + (if self.project_search_handle.is_some() { 1 } else { 0 })
+            + (if self.project_replace_handle.is_some() { 1 } else { 0 })
+            + (if self.messages_handle.is_some() { 1 } else { 0 })
     }
 
     pub fn event_sink(&self) -> IChannel {
@@ -507,21 +956,233 @@ impl Interface {
         self.siv.add_layer(buffer_list);
     }
 
+    fn show_history(&mut self) {
+        if self.history_list_handle.is_some() {
+            debug!("show_history: not showing history list, because it's already opened.");
+            return;
+        }
+
+        let revisions = self.active_editor().buffer_obs().borrow_content().revisions();
+        let memory_usage = self.active_editor().buffer_obs().history_memory_usage();
+        let items : Vec<Rc<ViewItem>> = revisions_to_items(&revisions, memory_usage)
+            .into_iter()
+            .map(|item| Rc::new(item))
+            .collect();
+
+        let mut history_list = FuzzyQueryView::new(
+            Arc::new(RefCell::new(SimpleIndex::new(items))),
+            HISTORY_LIST_MARKER.to_string(),
+            self.event_sink(),
+            self.settings_rc().clone(),
+            self.inot(),
+        );
+
+        self.history_list_handle = Some(history_list.get_mut().handle().clone());
+        self.siv.add_layer(history_list);
+    }
+
+    fn show_kill_ring(&mut self) {
+        if self.kill_ring_handle.is_some() {
+            debug!("show_kill_ring: not showing kill ring, because it's already opened.");
+            return;
+        }
+
+        let items : Vec<Rc<ViewItem>> = kill_ring_to_items(&self.state.kill_ring_rc().borrow())
+            .into_iter()
+            .map(|item| Rc::new(item))
+            .collect();
+
+        let mut kill_ring_list = FuzzyQueryView::new(
+            Arc::new(RefCell::new(SimpleIndex::new(items))),
+            KILL_RING_MARKER.to_string(),
+            self.event_sink(),
+            self.settings_rc().clone(),
+            self.inot(),
+        );
+
+        self.kill_ring_handle = Some(kill_ring_list.get_mut().handle().clone());
+        self.siv.add_layer(kill_ring_list);
+    }
+
+    fn show_project_search(&mut self) {
+        if self.project_search_handle.is_some() {
+            debug!("show_project_search: not showing project search, because it's already opened.");
+            return;
+        }
+
+        let mut project_search = FuzzyQueryView::new(
+            self.state.project_search_index(),
+            PROJECT_SEARCH_MARKER.to_string(),
+            self.event_sink(),
+            self.settings_rc().clone(),
+            self.inot(),
+        );
+
+        self.project_search_handle = Some(project_search.get_mut().handle().clone());
+        self.siv.add_layer(project_search);
+    }
+
+    fn show_project_replace(&mut self, query : &str, options : &SearchOptions, replacement : &str) {
+        if self.project_replace_handle.is_some() {
+            debug!("show_project_replace: not showing preview, because it's already opened.");
+            return;
+        }
+
+        let files = match self.state.project_replacements(query, options, replacement) {
+            Ok(files) => files,
+            Err(e) => {
+                let text = format!("unable to search project for \"{}\", because \"{}\"", query, e);
+                self.notify(Severity::Error, text);
+                return;
+            }
+        };
+        if files.is_empty() {
+            self.notify(Severity::Info, format!("project replace: no matches of \"{}\"", query));
+            return;
+        }
+
+        let mut project_replace = ProjectReplaceView::new(files, self.settings_rc().clone());
+
+        self.project_replace_handle = Some(project_replace.get_mut().handle().clone());
+        self.siv.add_layer(project_replace);
+    }
+
+    fn show_messages(&mut self) {
+        if self.messages_handle.is_some() {
+            debug!("show_messages: not showing messages, because they're already opened.");
+            return;
+        }
+
+        let items : Vec<Rc<ViewItem>> =
+            notifications_to_items(&self.state.notifications_rc().borrow())
+                .into_iter()
+                .map(|item| Rc::new(item))
+                .collect();
+
+        let mut messages = FuzzyQueryView::new(
+            Arc::new(RefCell::new(SimpleIndex::new(items))),
+            MESSAGES_MARKER.to_string(),
+            self.event_sink(),
+            self.settings_rc().clone(),
+            self.inot(),
+        );
+
+        self.messages_handle = Some(messages.get_mut().handle().clone());
+        self.siv.add_layer(messages);
+    }
+
+    /// Opens log file in a read-only buffer, which follows the file as it grows.
+    fn show_log(&mut self) {
+        match self.state.open_log() {
+            Ok(buffer_id) => {
+                self.open_and_or_focus(&buffer_id);
+                self.active_editor().go_to_end();
+            }
+            Err(e) => {
+                self.notify(Severity::Error, format!("unable to open log, because \"{}\"", e))
+            }
+        }
+    }
+
+    /// Shows message to the user in status bar of text views, and keeps it in message history.
+    fn notify(&mut self, severity : Severity, text : String) {
+        (**self.state.notifications_rc()).borrow_mut().post(severity, text);
+    }
+
+    /// Opens project search results as an editable buffer.
+    fn show_search_results(&mut self, query : &str, options : &SearchOptions) {
+        let matches = match self.state.search_project(query, options) {
+            Ok(matches) => matches,
+            Err(e) => {
+                let text = format!("unable to search project for \"{}\", because \"{}\"", query, e);
+                self.notify(Severity::Error, text);
+                return;
+            }
+        };
+        if matches.is_empty() {
+            self.notify(Severity::Info, format!("project search: no matches of \"{}\"", query));
+            return;
+        }
+
+        let buffer_id = self.state.open_search_results(matches);
+        self.open_and_or_focus(&buffer_id);
+    }
+
+    /// Pastes an older kill ring entry in active editor, moving it to the top of the ring.
+    fn paste_from_kill_ring(&mut self, idx : usize) {
+        let pieces_op = self.state.kill_ring_rc().borrow().get(idx).cloned();
+        match pieces_op {
+            Some(pieces) => {
+                (**self.state.kill_ring_rc()).borrow_mut().push(pieces.clone());
+                self.active_editor().paste_pieces(pieces);
+            }
+            None => error!("no kill ring entry {}", idx),
+        }
+    }
+
     fn enable_lsp(&mut self) {
         let lsp =
             LspClient::new(OsStr::new("rls"), self.event_sink(), Some(self.state.directories()));
-        self.lsp_clients.push(lsp.unwrap());
+        match lsp {
+            Ok(lsp) => {
+                self.state.lsp_status_rc().set(LspStatus::Starting);
+                self.lsp_clients.push(lsp);
+            }
+            Err(e) => {
+                let text = format!("starting language server failed, because \"{}\"", e);
+                self.notify(Severity::Error, text);
+                self.state.lsp_status_rc().set(LspStatus::Failed);
+            }
+        }
     }
 
     fn save_current_buffer(&mut self) {
-        let path = self.active_editor().buffer_obs().get_path();
-        if path.is_none() {
-            self.show_save_as();
-        } else {
-            let editor = self.active_editor();
-            let mut buffer = editor.buffer_obs().borrow_state();
-            let buffer_id = buffer.id();
-            debug!("save_current_buffer unimplemented ");
+        let buffer_id = self.active_editor().buffer_obs().buffer_id();
+        if self.state.is_search_results(&buffer_id) {
+            let write_back = match self.state.save_search_results(&buffer_id) {
+                Ok(write_back) => write_back,
+                Err(e) => {
+                    self.notify(
+                        Severity::Error,
+                        format!("writing search results back failed, because \"{}\"", e),
+                    );
+                    return;
+                }
+            };
+
+            for (edited_id, events) in write_back.buffer_edits {
+                self.apply_edit_events(&edited_id, events);
+            }
+
+            match (write_back.error_op, write_back.num_skipped) {
+                (Some(e), _) => self.notify(
+                    Severity::Error,
+                    format!("writing search results back failed, because \"{}\"", e),
+                ),
+                (None, 0) => self.notify(
+                    Severity::Info,
+                    format!("{} search result line(s) written back", write_back.num_written),
+                ),
+                (None, num_skipped) => self.notify(
+                    Severity::Warning,
+                    format!(
+                        "{} search result line(s) written back, {} skipped (see log)",
+                        write_back.num_written, num_skipped
+                    ),
+                ),
+            }
+            return;
+        }
+
+        let path_op = self.active_editor().buffer_obs().get_path();
+        match path_op {
+            None => self.show_save_as(),
+            Some(path) => match self.state.save_buffer_as(&buffer_id, path.clone()) {
+                Ok(()) => self.notify(Severity::Info, format!("saved {:?}", path)),
+                Err(e) => {
+                    self.notify(Severity::Error, format!("file save failed, because \"{}\"", e))
+                }
+            },
         }
     }
 
@@ -0,0 +1,70 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Kill ring is an internal clipboard with history. It's shared by all views, and it's the only
+// clipboard available if system one cannot be reached (no X, remote session etc.).
+
+use std::collections::VecDeque;
+
+const KILL_RING_SIZE : usize = 32;
+
+pub struct KillRing {
+    // Most recent entry first. Every entry holds one piece of text per cursor it was copied from.
+    entries : VecDeque<Vec<String>>,
+}
+
+impl KillRing {
+    pub fn new() -> Self {
+        KillRing { entries : VecDeque::new() }
+    }
+
+    /// Adds a new entry on top. If the same entry was already on the ring, it's moved to top.
+    pub fn push(&mut self, pieces : Vec<String>) {
+        if pieces.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|entry| *entry != pieces);
+        self.entries.push_front(pieces);
+        self.entries.truncate(KILL_RING_SIZE);
+    }
+
+    pub fn top(&self) -> Option<&Vec<String>> {
+        self.entries.front()
+    }
+
+    /// Returns entry by index, 0 being the most recent one.
+    pub fn get(&self, idx : usize) -> Option<&Vec<String>> {
+        self.entries.get(idx)
+    }
+
+    pub fn entries(&self) -> &VecDeque<Vec<String>> {
+        &self.entries
+    }
+}
+
+/// Joins pieces copied from multiple cursors into a single text, one piece per line. Pieces that
+/// are whole lines already end with a newline, so they get no additional separator.
+pub fn join_pieces(pieces : &Vec<String>) -> String {
+    let mut result = String::new();
+    for (idx, piece) in pieces.iter().enumerate() {
+        if idx > 0 && !result.ends_with('\n') {
+            result.push('\n');
+        }
+        result.push_str(piece);
+    }
+    result
+}
@@ -0,0 +1,270 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Maps chars of buffer lines to screen columns and rows. Lines are split into grapheme clusters,
+// each taking as many columns as unicode-width says (but at least one), tabs expanding to the next
+// tab stop. With soft wrap, lines are split into rows of view as well.
+//
+// Char ranges are relative to the beginning of line, columns to the beginning of row. A line is
+// drawn with it's newline, and the last line of buffer gets an extra cell after it's last char, so
+// there is always room for a cursor at the end.
+
+use ropey::Rope;
+use std::cmp;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Grapheme cluster of a line, as drawn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grapheme {
+    pub text :   String,
+    pub chars :  Range<usize>,
+    pub column : usize,
+    pub width :  usize,
+}
+
+impl Grapheme {
+    pub fn is_tab(&self) -> bool {
+        self.text == "\t"
+    }
+
+    fn is_blank(&self) -> bool {
+        self.text.chars().all(|c| c.is_whitespace())
+    }
+}
+
+/// Part of a buffer line drawn in a single row of view.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayRow {
+    pub line :            usize,
+    pub chars :           Range<usize>,
+    pub graphemes :       Vec<Grapheme>,
+    pub is_continuation : bool, // does not start at the beginning of line
+    pub wraps :           bool, // line goes on in the next row
+}
+
+impl DisplayRow {
+    /// Returns number of columns the row takes.
+    pub fn width(&self) -> usize {
+        self.graphemes.last().map(|g| g.column + g.width).unwrap_or(0)
+    }
+
+    /// Returns column char is drawn at. Chars inside a grapheme share it's column, chars after the
+    /// row get the column right after it.
+    pub fn column_of_char(&self, char_idx : usize) -> usize {
+        self.graphemes
+            .iter()
+            .find(|g| g.chars.end > char_idx)
+            .map(|g| g.column)
+            .unwrap_or(self.width())
+    }
+
+    /// Returns first char of grapheme drawn at column, or of the last one if column is past the
+    /// end of row.
+    pub fn char_at_column(&self, column : usize) -> usize {
+        self.graphemes
+            .iter()
+            .find(|g| g.column + g.width > column)
+            .or(self.graphemes.last())
+            .map(|g| g.chars.start)
+            .unwrap_or(self.chars.start)
+    }
+}
+
+/// Splits line (with it's newline) into grapheme clusters. Given tab width, columns (relative to
+/// the beginning of line) and widths are computed too, otherwise both are left at zero. This is
+/// the only place lines are split, so cursor movement and drawing agree on graphemes.
+pub fn line_graphemes(rope : &Rope, line : usize, tab_width_op : Option<usize>) -> Vec<Grapheme> {
+    let line_string = rope.line(line).to_string();
+    let mut result : Vec<Grapheme> = Vec::new();
+    let mut char_idx : usize = 0;
+    let mut column : usize = 0;
+    for text in line_string.graphemes(true) {
+        let len_chars = text.chars().count();
+        let width = match tab_width_op {
+            Some(tab_width) if text == "\t" => {
+                let tab_width = cmp::max(tab_width, 1);
+                tab_width - column % tab_width
+            }
+            Some(_) => cmp::max(text.width(), 1),
+            None => 0,
+        };
+        result.push(Grapheme {
+            text :   text.to_string(),
+            chars :  char_idx..char_idx + len_chars,
+            column : column,
+            width :  width,
+        });
+        char_idx += len_chars;
+        column += width;
+    }
+    result
+}
+
+// Splits graphemes into rows of at most width columns, breaking after whitespace if there is any
+// in the row, anywhere otherwise. Zero width means no wrapping. Returns ranges of graphemes.
+fn wrap_graphemes(graphemes : &[Grapheme], width : usize) -> Vec<Range<usize>> {
+    let mut result : Vec<Range<usize>> = Vec::new();
+    let mut begin : usize = 0;
+    let mut idx : usize = 0;
+    while idx < graphemes.len() {
+        let row_width = graphemes[idx].column + graphemes[idx].width - graphemes[begin].column;
+        if width > 0 && idx > begin && row_width > width {
+            let end = (begin + 1..idx + 1)
+                .rev()
+                .find(|&end| graphemes[end - 1].is_blank())
+                .unwrap_or(idx);
+            result.push(begin..end);
+            begin = end;
+            idx = end;
+        } else {
+            idx += 1;
+        }
+    }
+    result.push(begin..graphemes.len());
+    result
+}
+
+/// Returns rows line takes when wrapped at width (0 meaning no wrapping). Never empty.
+pub fn line_rows(rope : &Rope, line : usize, tab_width : usize, width : usize) -> Vec<DisplayRow> {
+    let mut graphemes = line_graphemes(rope, line, Some(tab_width));
+    if line + 1 == rope.len_lines() {
+        // room for cursor at the end of buffer.
+        let (char_idx, column) =
+            graphemes.last().map(|g| (g.chars.end, g.column + g.width)).unwrap_or((0, 0));
+        graphemes.push(Grapheme {
+            text :   " ".to_string(),
+            chars :  char_idx..char_idx + 1,
+            column : column,
+            width :  1,
+        });
+    }
+    let ranges = wrap_graphemes(&graphemes, width);
+    let num_rows = ranges.len();
+    ranges
+        .into_iter()
+        .enumerate()
+        .map(|(idx, range)| {
+            let first_column = graphemes.get(range.start).map(|g| g.column).unwrap_or(0);
+            let row_graphemes : Vec<Grapheme> = graphemes[range]
+                .iter()
+                .map(|g| Grapheme { column : g.column - first_column, ..g.clone() })
+                .collect();
+            let chars = match (row_graphemes.first(), row_graphemes.last()) {
+                (Some(first), Some(last)) => first.chars.start..last.chars.end,
+                _ => 0..0,
+            };
+            DisplayRow {
+                line :            line,
+                chars :           chars,
+                graphemes :       row_graphemes,
+                is_continuation : idx > 0,
+                wraps :           idx + 1 < num_rows,
+            }
+        })
+        .collect()
+}
+
+/// Returns index of row given char of line is drawn in. Char at the end of a row that wraps
+/// belongs to the next one.
+pub fn row_of_char(rows : &[DisplayRow], char_idx : usize) -> usize {
+    rows.iter().rposition(|row| row.chars.start <= char_idx).unwrap_or(0)
+}
+
+/// Returns at most height rows of view, starting with the first row of first_line.
+pub fn display_rows(
+    rope : &Rope,
+    first_line : usize,
+    tab_width : usize,
+    width : usize,
+    height : usize,
+) -> Vec<DisplayRow> {
+    let mut result : Vec<DisplayRow> = Vec::new();
+    let mut line = first_line;
+    while result.len() < height && line < rope.len_lines() {
+        for row in line_rows(rope, line, tab_width, width) {
+            if result.len() == height {
+                break;
+            }
+            result.push(row);
+        }
+        line += 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row_chars(rows : &[DisplayRow]) -> Vec<Range<usize>> {
+        rows.iter().map(|row| row.chars.clone()).collect()
+    }
+
+    #[test]
+    fn lines_wrap_after_whitespace_or_at_width() {
+        let rope = Rope::from_str("lorem ipsum dolor\nabcdefghij\n");
+
+        assert_eq!(row_chars(&line_rows(&rope, 0, 4, 8)), vec![0..6, 6..12, 12..18]);
+        assert_eq!(row_chars(&line_rows(&rope, 1, 4, 4)), vec![0..4, 4..8, 8..11]);
+        assert_eq!(row_chars(&line_rows(&rope, 1, 4, 0)), vec![0..11]);
+        assert_eq!(row_chars(&line_rows(&rope, 2, 4, 4)), vec![0..1]);
+
+        let rows = line_rows(&rope, 0, 4, 8);
+        assert_eq!(row_of_char(&rows, 5), 0);
+        assert_eq!(row_of_char(&rows, 6), 1);
+        assert_eq!(row_of_char(&rows, 17), 2);
+    }
+
+    #[test]
+    fn display_rows_stop_at_height() {
+        let rope = Rope::from_str("aaaa aaaa\nb");
+
+        let rows = display_rows(&rope, 0, 4, 5, 10);
+        assert_eq!(rows.len(), 3);
+        assert!(!rows[0].is_continuation && rows[0].wraps);
+        assert!(rows[1].is_continuation && !rows[1].wraps);
+        assert_eq!(rows[2].line, 1);
+        assert_eq!(rows[2].chars, 0..2);
+
+        assert_eq!(display_rows(&rope, 0, 4, 5, 1).len(), 1);
+    }
+
+    #[test]
+    fn wide_chars_combining_marks_and_tabs_map_to_columns() {
+        // "e" with combining acute accent is a single grapheme of two chars.
+        let rope = Rope::from_str("\u{4e2d}e\u{301}\tx\n");
+        let rows = line_rows(&rope, 0, 4, 0);
+        let row = &rows[0];
+
+        let columns : Vec<(usize, usize)> =
+            row.graphemes.iter().map(|g| (g.column, g.width)).collect();
+        assert_eq!(columns, vec![(0, 2), (2, 1), (3, 1), (4, 1), (5, 1)]);
+
+        assert_eq!(row.column_of_char(1), 2);
+        assert_eq!(row.column_of_char(2), 2);
+        assert_eq!(row.column_of_char(4), 4);
+        assert_eq!(row.char_at_column(1), 0);
+        assert_eq!(row.char_at_column(3), 3);
+        assert_eq!(row.char_at_column(100), 5);
+
+        let rope = Rope::from_str("a\tb");
+        let rows = line_rows(&rope, 0, 8, 0);
+        assert_eq!(rows[0].graphemes[1].width, 7);
+        assert_eq!(rows[0].column_of_char(2), 8);
+    }
+}
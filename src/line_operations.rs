@@ -0,0 +1,425 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Operations working on whole lines covered by cursors. Each one returns events (in order they
+// are to be applied, so they can be submitted as one batch) and cursors after the change, or None
+// if there is nothing to do.
+//
+// Line "blocks" are inclusive ranges of lines touched by cursors. A selection ending at the very
+// beginning of a line does not touch that line.
+
+use content_provider::EditEvent;
+use cursor::{map_cursors, Cursor};
+use ropey::Rope;
+use std::cmp;
+use std::collections::BTreeSet;
+use text_navigation::line_end_without_newline;
+
+pub type LineOperationResult = Option<(Vec<EditEvent>, Vec<Cursor>)>;
+
+fn cursor_lines(rope : &Rope, c : &Cursor) -> (usize, usize) {
+    let first = rope.char_to_line(c.begin());
+    let mut last = rope.char_to_line(c.end());
+    if last > first && rope.line_to_char(last) == c.end() {
+        last -= 1;
+    }
+    (first, last)
+}
+
+// Sorted blocks, overlapping and adjacent ones are merged.
+fn line_blocks(rope : &Rope, cursors : &Vec<Cursor>) -> Vec<(usize, usize)> {
+    let mut blocks : Vec<(usize, usize)> = cursors.iter().map(|c| cursor_lines(rope, c)).collect();
+    blocks.sort();
+
+    let mut result : Vec<(usize, usize)> = Vec::new();
+    for (first, last) in blocks {
+        if let Some(prev) = result.last_mut() {
+            if first <= prev.1 + 1 {
+                prev.1 = cmp::max(prev.1, last);
+                continue;
+            }
+        }
+        result.push((first, last));
+    }
+    result
+}
+
+// Text of lines first..=last, including the final newline if there is one.
+fn lines_text(rope : &Rope, first : usize, last : usize) -> String {
+    rope.slice(rope.line_to_char(first)..rope.line_to_char(last + 1)).to_string()
+}
+
+fn indentation_of(rope : &Rope, line : usize) -> String {
+    rope.line(line).chars().take_while(|c| *c == ' ' || *c == '\t').collect()
+}
+
+fn shift_cursor(c : &Cursor, shift : isize) -> Cursor {
+    let mut result = *c;
+    result.offset = (c.offset as isize + shift) as usize;
+    result.anchor = c.anchor.map(|anchor| (anchor as isize + shift) as usize);
+    result
+}
+
+/// Duplicates selections, or lines of cursors without one. Copy is inserted before the original,
+/// so cursors end up on the later one.
+pub fn duplicate(rope : &Rope, cursors : &Vec<Cursor>) -> LineOperationResult {
+    let mut inserts : Vec<(usize, String)> = Vec::new();
+    for c in cursors {
+        let insert = match c.selection() {
+            Some(selection) => (selection.start, rope.slice(selection).to_string()),
+            None => {
+                let line = rope.char_to_line(c.offset);
+                let mut text = lines_text(rope, line, line);
+                if !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                (rope.line_to_char(line), text)
+            }
+        };
+        if !inserts.contains(&insert) {
+            inserts.push(insert);
+        }
+    }
+
+    // descending order keeps offsets of remaining events valid.
+    inserts.sort_by(|a, b| b.0.cmp(&a.0));
+    let events : Vec<EditEvent> = inserts
+        .into_iter()
+        .map(|(offset, content)| EditEvent::Insert { offset, content })
+        .collect();
+
+    let mut new_cursors = cursors.clone();
+    map_cursors(&mut new_cursors, &events);
+    Some((events, new_cursors))
+}
+
+// Swaps every block with the line above (up == true) or below it.
+fn move_lines(rope : &Rope, cursors : &Vec<Cursor>, up : bool) -> LineOperationResult {
+    let blocks = line_blocks(rope, cursors);
+    let mut events : Vec<EditEvent> = Vec::new();
+    // (first line of block, last line of block, shift of its offsets)
+    let mut shifts : Vec<(usize, usize, isize)> = Vec::new();
+
+    for &(first, last) in blocks.iter().rev() {
+        let (neighbour, region_first, region_last) = if up {
+            if first == 0 {
+                continue;
+            }
+            (first - 1, first - 1, last)
+        } else {
+            if last + 1 >= rope.len_lines() {
+                continue;
+            }
+            (last + 1, first, last + 1)
+        };
+
+        let mut block_text = lines_text(rope, first, last);
+        let mut neighbour_text = lines_text(rope, neighbour, neighbour);
+        // last line of file has no newline, so it has to be moved from the other part.
+        if !block_text.ends_with('\n') {
+            block_text.push('\n');
+            neighbour_text.pop();
+        } else if !neighbour_text.ends_with('\n') {
+            neighbour_text.push('\n');
+            block_text.pop();
+        }
+
+        let region_begin = rope.line_to_char(region_first);
+        let region_end = rope.line_to_char(region_last + 1);
+        let new_block_begin = if up {
+            region_begin
+        } else {
+            region_begin + neighbour_text.chars().count()
+        };
+
+        shifts.push((first, last, new_block_begin as isize - rope.line_to_char(first) as isize));
+        let content = if up { block_text + &neighbour_text } else { neighbour_text + &block_text };
+        events.push(EditEvent::Change {
+            offset :  region_begin,
+            length :  region_end - region_begin,
+            content : content,
+        });
+    }
+
+    if events.is_empty() {
+        return None;
+    }
+
+    // moved regions keep their lengths, so cursors outside them stay where they were.
+    let new_cursors : Vec<Cursor> = cursors
+        .iter()
+        .map(|c| {
+            let (line, _) = cursor_lines(rope, c);
+            match shifts.iter().find(|&&(first, last, _)| first <= line && line <= last) {
+                Some(&(_, _, shift)) => shift_cursor(c, shift),
+                None => *c,
+            }
+        })
+        .collect();
+
+    Some((events, new_cursors))
+}
+
+pub fn move_lines_up(rope : &Rope, cursors : &Vec<Cursor>) -> LineOperationResult {
+    move_lines(rope, cursors, true)
+}
+
+pub fn move_lines_down(rope : &Rope, cursors : &Vec<Cursor>) -> LineOperationResult {
+    move_lines(rope, cursors, false)
+}
+
+/// Joins lines of every block, or a cursor's line with the next one. Whitespace around the joint
+/// is replaced with a single space.
+pub fn join_lines(rope : &Rope, cursors : &Vec<Cursor>) -> LineOperationResult {
+    // lines to be joined with the next one.
+    let mut joints : BTreeSet<usize> = BTreeSet::new();
+    for (first, last) in line_blocks(rope, cursors) {
+        let last = if last == first { first + 1 } else { last };
+        for line in first..last {
+            if line + 1 < rope.len_lines() {
+                joints.insert(line);
+            }
+        }
+    }
+
+    let mut events : Vec<EditEvent> = Vec::new();
+    for &line in joints.iter().rev() {
+        let line_begin = rope.line_to_char(line);
+        let mut begin = line_end_without_newline(rope, line);
+        while begin > line_begin && (rope.char(begin - 1) == ' ' || rope.char(begin - 1) == '\t') {
+            begin -= 1;
+        }
+
+        let next_line_begin = rope.line_to_char(line + 1);
+        let next_line_end = line_end_without_newline(rope, line + 1);
+        let next_line_begin_no_indent = next_line_begin + indentation_of(rope, line + 1).len();
+
+        let content = if begin == line_begin || next_line_begin_no_indent == next_line_end {
+            ""
+        } else {
+            " "
+        };
+
+        events.push(EditEvent::Change {
+            offset :  begin,
+            length :  next_line_begin_no_indent - begin,
+            content : content.to_string(),
+        });
+    }
+
+    if events.is_empty() {
+        return None;
+    }
+
+    let mut new_cursors = cursors.clone();
+    map_cursors(&mut new_cursors, &events);
+    Some((events, new_cursors))
+}
+
+/// Removes lines of every block. Cursors land on the line after removed ones.
+pub fn delete_lines(rope : &Rope, cursors : &Vec<Cursor>) -> LineOperationResult {
+    let mut events : Vec<EditEvent> = Vec::new();
+    for (first, last) in line_blocks(rope, cursors).into_iter().rev() {
+        let mut begin = rope.line_to_char(first);
+        let end = rope.line_to_char(last + 1);
+        // removing last lines would leave an empty line behind, so newline before them goes too.
+        if last + 1 == rope.len_lines() && first > 0 {
+            begin = line_end_without_newline(rope, first - 1);
+        }
+
+        if begin != end {
+            events.push(EditEvent::Change {
+                offset :  begin,
+                length :  end - begin,
+                content : "".to_string(),
+            });
+        }
+    }
+
+    if events.is_empty() {
+        return None;
+    }
+
+    let mut new_cursors : Vec<Cursor> = cursors.iter().map(|c| Cursor::new(c.offset)).collect();
+    map_cursors(&mut new_cursors, &events);
+    Some((events, new_cursors))
+}
+
+/// Opens a new line below (or above) line of every cursor, keeping it's indentation. Cursors
+/// move to the new lines.
+pub fn insert_line(rope : &Rope, cursors : &Vec<Cursor>, above : bool) -> LineOperationResult {
+    let lines : BTreeSet<usize> = cursors.iter().map(|c| rope.char_to_line(c.offset)).collect();
+
+    let mut events : Vec<EditEvent> = Vec::new();
+    let mut new_cursors : Vec<Cursor> = Vec::new();
+    let mut shift : usize = 0; // sum of lengths of inserts before current one.
+    for line in lines {
+        let indentation = indentation_of(rope, line);
+        let indentation_len = indentation.chars().count();
+        let (offset, content, cursor_offset) = if above {
+            let offset = rope.line_to_char(line);
+            (offset, indentation + "\n", offset + indentation_len)
+        } else {
+            let offset = line_end_without_newline(rope, line);
+            (offset, "\n".to_string() + &indentation, offset + 1 + indentation_len)
+        };
+
+        new_cursors.push(Cursor::new(cursor_offset + shift));
+        shift += content.chars().count();
+        events.push(EditEvent::Insert { offset, content });
+    }
+
+    events.reverse();
+    Some((events, new_cursors))
+}
+
+/// Adds indent_string at the beginning of every non-empty line of every block.
+pub fn indent(rope : &Rope, cursors : &Vec<Cursor>, indent_string : &str) -> LineOperationResult {
+    let mut events : Vec<EditEvent> = Vec::new();
+    for (first, last) in line_blocks(rope, cursors).into_iter().rev() {
+        for line in (first..(last + 1)).rev() {
+            let line_begin = rope.line_to_char(line);
+            if line_begin != line_end_without_newline(rope, line) {
+                events.push(EditEvent::Insert {
+                    offset :  line_begin,
+                    content : indent_string.to_string(),
+                });
+            }
+        }
+    }
+
+    if events.is_empty() {
+        return None;
+    }
+
+    let mut new_cursors = cursors.clone();
+    map_cursors(&mut new_cursors, &events);
+    Some((events, new_cursors))
+}
+
+/// Removes one level of indentation (indent_string, or a part of it, or a single tab) from every
+/// line of every block.
+pub fn dedent(rope : &Rope, cursors : &Vec<Cursor>, indent_string : &str) -> LineOperationResult {
+    let indent_len = indent_string.chars().count();
+    let mut events : Vec<EditEvent> = Vec::new();
+    for (first, last) in line_blocks(rope, cursors).into_iter().rev() {
+        for line in (first..(last + 1)).rev() {
+            let indentation : Vec<char> = indentation_of(rope, line).chars().collect();
+            let length = if indentation.first() == Some(&'\t') {
+                1
+            } else {
+                indentation.iter().take(indent_len).take_while(|c| **c == ' ').count()
+            };
+
+            if length > 0 {
+                events.push(EditEvent::Change {
+                    offset :  rope.line_to_char(line),
+                    length :  length,
+                    content : "".to_string(),
+                });
+            }
+        }
+    }
+
+    if events.is_empty() {
+        return None;
+    }
+
+    let mut new_cursors = cursors.clone();
+    map_cursors(&mut new_cursors, &events);
+    Some((events, new_cursors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(rope : &Rope, result : LineOperationResult) -> (String, Vec<Cursor>) {
+        let (events, cursors) = result.unwrap();
+        let mut rope = rope.clone();
+        for event in events {
+            match event {
+                EditEvent::Insert { offset, content } => rope.insert(offset, &content),
+                EditEvent::Change { offset, length, content } => {
+                    rope.remove(offset..(offset + length));
+                    rope.insert(offset, &content);
+                }
+            }
+        }
+        (rope.to_string(), cursors)
+    }
+
+    #[test]
+    fn duplicate_line_and_selection() {
+        let rope = Rope::from_str("abc\ndef");
+        let cursors = vec![Cursor::new(1), Cursor::with_selection(4, 6)];
+        let (text, cursors) = apply(&rope, duplicate(&rope, &cursors));
+
+        assert_eq!(text, "abc\nabc\ndedef");
+        assert_eq!(cursors, vec![Cursor::new(5), Cursor::with_selection(10, 12)]);
+    }
+
+    #[test]
+    fn move_lines_up_and_down_around_last_line() {
+        let rope = Rope::from_str("a\nbb\ncc");
+        let (text, cursors) = apply(&rope, move_lines_up(&rope, &vec![Cursor::new(6)]));
+        assert_eq!(text, "a\ncc\nbb");
+        assert_eq!(cursors, vec![Cursor::new(3)]);
+
+        let (text, cursors) = apply(&rope, move_lines_down(&rope, &vec![Cursor::new(1)]));
+        assert_eq!(text, "bb\na\ncc");
+        assert_eq!(cursors, vec![Cursor::new(4)]);
+
+        assert!(move_lines_up(&rope, &vec![Cursor::new(0)]).is_none());
+        assert!(move_lines_down(&rope, &vec![Cursor::new(6)]).is_none());
+    }
+
+    #[test]
+    fn join_and_delete_lines() {
+        let rope = Rope::from_str("fn a() {  \n    b();\n}\n");
+        let (text, _) = apply(&rope, join_lines(&rope, &vec![Cursor::new(0)]));
+        assert_eq!(text, "fn a() { b();\n}\n");
+
+        let (text, cursors) = apply(&rope, delete_lines(&rope, &vec![Cursor::new(12)]));
+        assert_eq!(text, "fn a() {  \n}\n");
+        assert_eq!(cursors, vec![Cursor::new(11)]);
+    }
+
+    #[test]
+    fn insert_lines_keep_indentation() {
+        let rope = Rope::from_str("a\n  b\nc");
+        let cursors = vec![Cursor::new(0), Cursor::new(4)];
+        let (text, cursors) = apply(&rope, insert_line(&rope, &cursors, false));
+        assert_eq!(text, "a\n\n  b\n  \nc");
+        assert_eq!(cursors, vec![Cursor::new(2), Cursor::new(9)]);
+
+        let cursors = vec![Cursor::new(4)];
+        let (text, cursors) = apply(&rope, insert_line(&rope, &cursors, true));
+        assert_eq!(text, "a\n  \n  b\nc");
+        assert_eq!(cursors, vec![Cursor::new(4)]);
+    }
+
+    #[test]
+    fn indent_and_dedent_selected_lines() {
+        let rope = Rope::from_str("a\n\n  b\nc");
+        let cursors = vec![Cursor::with_selection(0, 7)];
+        let (text, _) = apply(&rope, indent(&rope, &cursors, "    "));
+        assert_eq!(text, "    a\n\n      b\nc");
+
+        let (text, _) = apply(&rope, dedent(&rope, &cursors, "    "));
+        assert_eq!(text, "a\n\nb\nc");
+    }
+}
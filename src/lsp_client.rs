@@ -39,6 +39,7 @@ use events::IEvent;
 use jsonrpc_core::types as jt;
 use jsonrpc_core::Output;
 use languageserver_types;
+use notifications::Severity;
 
 pub struct LspClient {
     waiter_handle :  JoinHandle<()>,
@@ -52,6 +53,15 @@ pub enum LSPEvent {
     Initialized,
 }
 
+/// State of language server, as shown in status bar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LspStatus {
+    Off,
+    Starting,
+    Ready,
+    Failed,
+}
+
 const ID_INIT : u64 = 0; // it's always a first message.
 const ID_COMPLETION : u64 = 0;
 
@@ -101,6 +111,7 @@ impl LspClient {
 
         let lsp_channel = channel::<LSPEvent>();
         let lsp_sink = lsp_channel.0.clone();
+        let status_sink = event_sink.clone();
 
         let handle = thread::spawn(move || {
             let mut headers : HashMap<String, String> = HashMap::new();
@@ -109,6 +120,8 @@ impl LspClient {
                 loop {
                     let mut header = String::new();
                     if reader.read_line(&mut header).unwrap() == 0 {
+                        debug!("lsp: server closed it's output");
+                        status_sink.send(IEvent::LspStatusChanged(LspStatus::Off)).unwrap();
                         return;
                     }
                     let header = header.trim();
@@ -117,6 +130,9 @@ impl LspClient {
                     }
                     let parts : Vec<&str> = header.split(": ").collect();
                     if parts.len() != 2 {
+                        let text = format!("language server sent malformed header {:?}", header);
+                        status_sink.send(IEvent::Notify(Severity::Error, text)).unwrap();
+                        status_sink.send(IEvent::LspStatusChanged(LspStatus::Failed)).unwrap();
                         return;
                     }
                     headers.insert(parts[0].to_string(), parts[1].to_string());
@@ -133,6 +149,7 @@ impl LspClient {
                     Ok(jt::Output::Success(suc)) => {
                         if suc.id == jsonrpc_core::id::Id::Num(ID_INIT) {
                             lsp_sink.send(LSPEvent::Initialized).unwrap();
+                            status_sink.send(IEvent::LspStatusChanged(LspStatus::Ready)).unwrap();
                         } else if suc.id == jsonrpc_core::id::Id::Num(ID_COMPLETION) {
                             let completion = serde_json::from_value::<
                                 languageserver_types::CompletionResponse,
@@ -41,24 +41,39 @@ mod buffer_id;
 mod buffer_index;
 mod buffer_state;
 mod buffer_state_observer;
+mod clipboard_backend;
 mod color_view_wrapper;
 mod content_provider;
+mod cursor;
 mod default_settings;
 mod events;
 mod file_dialog;
+mod file_logger;
 mod fuzzy_index;
 mod fuzzy_index_trait;
 mod fuzzy_query_view;
 mod fuzzy_view_item;
 mod interface;
+mod kill_ring;
+mod line_layout;
+mod line_operations;
 mod dir_tree;
 mod lsp_client;
+mod notifications;
 mod overlay_dialog;
+mod pane_layout;
+mod pane_view;
+mod project_replace_view;
+mod project_search;
 mod rich_content;
+mod search;
 mod settings;
 mod simple_fuzzy_index;
 mod sly_text_view;
 mod sly_view;
+mod special_chars;
+mod status_bar;
+mod text_navigation;
 mod view_handle;
 
 extern crate clipboard;
@@ -69,7 +84,6 @@ extern crate ignore;
 extern crate regex;
 extern crate ropey;
 extern crate serde_json;
-extern crate stderrlog;
 extern crate syntect;
 extern crate time;
 extern crate unicode_segmentation;
@@ -123,10 +137,38 @@ fn get_file_list_from_dir(path : &Path) -> Vec<String> {
     file_list
 }
 
+// Returns path of log file, None if logging is off or file could not be opened.
+fn init_logging(matches : &clap::ArgMatches) -> Option<PathBuf> {
+    let level = matches
+        .value_of("log_level")
+        .and_then(|level| level.parse::<log::LevelFilter>().ok())
+        .unwrap_or(log::LevelFilter::Info);
+    if level == log::LevelFilter::Off {
+        return None;
+    }
+
+    let path = match matches.value_of("log_file") {
+        Some(path) => PathBuf::from(path),
+        None => match file_logger::default_log_path() {
+            Some(path) => path,
+            None => {
+                eprintln!("unable to find data directory, logging is off.");
+                return None;
+            }
+        },
+    };
+
+    match file_logger::init(&path, level) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            eprintln!("unable to log to {:?}, because \"{}\"", path, e);
+            None
+        }
+    }
+}
+
 fn main() {
     //        setup_panic!();
-    stderrlog::new().module(module_path!()).verbosity(5).init().unwrap();
-
     let yml = clap::load_yaml!("clap.yml");
     let mut app = clap::App::from_yaml(yml)
         .author("Andrzej J Skalski <ajskalski@google.com>")
@@ -139,6 +181,8 @@ fn main() {
         return;
     }
 
+    let log_file : Option<PathBuf> = init_logging(&matches);
+
     let profiling_enabled : bool = matches.is_present("profiling");
     let git_files_included : bool = matches.is_present("git");
 
@@ -194,7 +238,7 @@ fn main() {
         &directories, &files, git_files_included
     );
 
-    let app_state = AppState::new(directories, files, git_files_included == false);
+    let app_state = AppState::new(directories, files, git_files_included == false, log_file);
 
     let mut interface = Interface::new(app_state);
     interface.main();
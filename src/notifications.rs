@@ -0,0 +1,111 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Messages for the user, like "file save failed". The most recent one is shown in the status row
+// of text view until it expires, older ones are kept in a history that can be browsed.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use time;
+
+const HISTORY_LENGTH : usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            &Severity::Info => "info",
+            &Severity::Warning => "warning",
+            &Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub severity :  Severity,
+    pub text :      String,
+    pub timestamp : time::Tm,
+    posted :        Instant,
+}
+
+pub struct Notifications {
+    history : VecDeque<Notification>, // oldest first
+    timeout : Duration,
+}
+
+impl Notifications {
+    pub fn new(timeout : Duration) -> Self {
+        Notifications { history : VecDeque::new(), timeout : timeout }
+    }
+
+    /// Stores message and writes it to the log as well.
+    pub fn post(&mut self, severity : Severity, text : String) {
+        match severity {
+            Severity::Info => info!("{}", text),
+            Severity::Warning => warn!("{}", text),
+            Severity::Error => error!("{}", text),
+        }
+
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(Notification {
+            severity :  severity,
+            text :      text,
+            timestamp : time::now(),
+            posted :    Instant::now(),
+        });
+    }
+
+    /// Returns the most recent message, unless it has already expired.
+    pub fn current(&self, now : Instant) -> Option<&Notification> {
+        self.history.back().filter(|notification| notification.posted + self.timeout > now)
+    }
+
+    pub fn history(&self) -> &VecDeque<Notification> {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_expire_but_stay_in_history() {
+        let mut notifications = Notifications::new(Duration::from_secs(5));
+        assert!(notifications.current(Instant::now()).is_none());
+
+        notifications.post(Severity::Info, "saved".to_string());
+        notifications.post(Severity::Error, "file save failed".to_string());
+        let now = Instant::now();
+        assert_eq!(notifications.current(now).map(|n| n.text.as_str()), Some("file save failed"));
+        assert!(notifications.current(now + Duration::from_secs(6)).is_none());
+
+        for idx in 0..HISTORY_LENGTH {
+            notifications.post(Severity::Warning, idx.to_string());
+        }
+        assert_eq!(notifications.history().len(), HISTORY_LENGTH);
+        assert_eq!(notifications.history()[0].text, "0");
+    }
+}
@@ -0,0 +1,375 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Arrangement of editor panes: a binary tree, every split dividing it's space between two
+// subtrees either horizontally (side by side, with a one column separator) or vertically (one
+// above another, status bar of the upper one acting as separator). Panes are identified by handles
+// of views they show, views themselves are kept by PaneView.
+
+use cursive::direction::Orientation;
+use cursive::vec::Vec2;
+use std::cmp;
+use std::mem;
+use view_handle::ViewHandle;
+
+const MIN_RATIO : usize = 10;
+const MAX_RATIO : usize = 90;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaneDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl PaneDirection {
+    fn orientation(&self) -> Orientation {
+        match self {
+            &PaneDirection::Left | &PaneDirection::Right => Orientation::Horizontal,
+            &PaneDirection::Up | &PaneDirection::Down => Orientation::Vertical,
+        }
+    }
+
+    // Whether direction points towards the first subtree of a split.
+    fn is_backward(&self) -> bool {
+        match self {
+            &PaneDirection::Left | &PaneDirection::Up => true,
+            &PaneDirection::Right | &PaneDirection::Down => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum PaneTree {
+    Pane(ViewHandle),
+    Split(Box<Split>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Split {
+    orientation : Orientation,
+    ratio :       usize, // percent of space taken by the first subtree
+    first :       PaneTree,
+    second :      PaneTree,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaneRect {
+    pub offset : Vec2,
+    pub size :   Vec2,
+}
+
+impl PaneRect {
+    pub fn contains(&self, position : Vec2) -> bool {
+        position.x >= self.offset.x
+            && position.y >= self.offset.y
+            && position.x < self.offset.x + self.size.x
+            && position.y < self.offset.y + self.size.y
+    }
+}
+
+/// Result of laying out panes in given space.
+#[derive(Clone, Debug, Default)]
+pub struct PaneLayout {
+    pub panes :      Vec<(ViewHandle, PaneRect)>,
+    pub separators : Vec<PaneRect>, // one column wide, between panes side by side
+}
+
+// Divides length between two parts, leaving room for separator.
+fn split_length(length : usize, ratio : usize, separator : usize) -> (usize, usize) {
+    let available = length.saturating_sub(separator);
+    let first = available * ratio / 100;
+    (first, available - first)
+}
+
+impl PaneTree {
+    pub fn new(pane : ViewHandle) -> Self {
+        PaneTree::Pane(pane)
+    }
+
+    fn is_pane(&self, handle : &ViewHandle) -> bool {
+        match self {
+            &PaneTree::Pane(ref pane) => pane == handle,
+            &PaneTree::Split(_) => false,
+        }
+    }
+
+    /// Returns handles of all panes, left to right and top to bottom.
+    pub fn panes(&self) -> Vec<ViewHandle> {
+        match self {
+            &PaneTree::Pane(ref pane) => vec![pane.clone()],
+            &PaneTree::Split(ref split) => {
+                let mut result = split.first.panes();
+                result.append(&mut split.second.panes());
+                result
+            }
+        }
+    }
+
+    /// Divides space of pane in two, putting new pane in given direction from it. Returns false if
+    /// there is no such pane.
+    pub fn split(
+        &mut self,
+        pane : &ViewHandle,
+        new_pane : &ViewHandle,
+        direction : PaneDirection,
+    ) -> bool {
+        match self {
+            &mut PaneTree::Pane(ref handle) if handle == pane => {}
+            &mut PaneTree::Pane(_) => return false,
+            &mut PaneTree::Split(ref mut split) => {
+                return split.first.split(pane, new_pane, direction)
+                    || split.second.split(pane, new_pane, direction)
+            }
+        }
+
+        let (first, second) = if direction.is_backward() {
+            (new_pane.clone(), pane.clone())
+        } else {
+            (pane.clone(), new_pane.clone())
+        };
+        *self = PaneTree::Split(Box::new(Split {
+            orientation : direction.orientation(),
+            ratio :       50,
+            first :       PaneTree::Pane(first),
+            second :      PaneTree::Pane(second),
+        }));
+        true
+    }
+
+    /// Removes pane, it's sibling taking the space of their split. Returns false if there is no
+    /// such pane, or it's the last one.
+    pub fn close(&mut self, pane : &ViewHandle) -> bool {
+        let sibling = match self {
+            &mut PaneTree::Pane(_) => return false,
+            &mut PaneTree::Split(ref mut split) => {
+                // Placeholder for the subtree moved out, the split is dropped right after.
+                let placeholder = PaneTree::Pane(pane.clone());
+                if split.first.is_pane(pane) {
+                    mem::replace(&mut split.second, placeholder)
+                } else if split.second.is_pane(pane) {
+                    mem::replace(&mut split.first, placeholder)
+                } else {
+                    return split.first.close(pane) || split.second.close(pane);
+                }
+            }
+        };
+        *self = sibling;
+        true
+    }
+
+    /// Makes pane show another view.
+    pub fn replace(&mut self, pane : &ViewHandle, new_pane : &ViewHandle) -> bool {
+        match self {
+            &mut PaneTree::Pane(ref mut handle) => {
+                if handle == pane {
+                    *handle = new_pane.clone();
+                    true
+                } else {
+                    false
+                }
+            }
+            &mut PaneTree::Split(ref mut split) => {
+                split.first.replace(pane, new_pane) || split.second.replace(pane, new_pane)
+            }
+        }
+    }
+
+    /// Moves the closest separator of pane that can move in given direction (that is, vertical one
+    /// for left and right) by step percent of space of it's split. Returns false if there is none.
+    pub fn resize(&mut self, pane : &ViewHandle, direction : PaneDirection, step : usize) -> bool {
+        self.resize_rec(pane, direction, step) == Some(true)
+    }
+
+    // None if pane is not in this subtree, Some(true) if a separator was moved.
+    fn resize_rec(
+        &mut self,
+        pane : &ViewHandle,
+        direction : PaneDirection,
+        step : usize,
+    ) -> Option<bool> {
+        match self {
+            &mut PaneTree::Pane(ref handle) => {
+                if handle == pane {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            &mut PaneTree::Split(ref mut split) => {
+                let result = match split.first.resize_rec(pane, direction, step) {
+                    None => split.second.resize_rec(pane, direction, step),
+                    result => result,
+                };
+                if result == Some(false) && split.orientation == direction.orientation() {
+                    split.ratio = if direction.is_backward() {
+                        cmp::max(split.ratio.saturating_sub(step), MIN_RATIO)
+                    } else {
+                        cmp::min(split.ratio + step, MAX_RATIO)
+                    };
+                    Some(true)
+                } else {
+                    result
+                }
+            }
+        }
+    }
+
+    /// Lays out panes in space of given size.
+    pub fn layout(&self, size : Vec2) -> PaneLayout {
+        let mut result = PaneLayout::default();
+        self.layout_rec(PaneRect { offset : Vec2::zero(), size : size }, &mut result);
+        result
+    }
+
+    fn layout_rec(&self, rect : PaneRect, result : &mut PaneLayout) {
+        let split = match self {
+            &PaneTree::Pane(ref handle) => {
+                result.panes.push((handle.clone(), rect));
+                return;
+            }
+            &PaneTree::Split(ref split) => split,
+        };
+
+        let (first_rect, second_rect) = match split.orientation {
+            Orientation::Horizontal => {
+                let (first, second) = split_length(rect.size.x, split.ratio, 1);
+                result.separators.push(PaneRect {
+                    offset : rect.offset + Vec2::new(first, 0),
+                    size :   Vec2::new(1, rect.size.y),
+                });
+                (
+                    PaneRect { offset : rect.offset, size : Vec2::new(first, rect.size.y) },
+                    PaneRect {
+                        offset : rect.offset + Vec2::new(first + 1, 0),
+                        size :   Vec2::new(second, rect.size.y),
+                    },
+                )
+            }
+            Orientation::Vertical => {
+                let (first, second) = split_length(rect.size.y, split.ratio, 0);
+                (
+                    PaneRect { offset : rect.offset, size : Vec2::new(rect.size.x, first) },
+                    PaneRect {
+                        offset : rect.offset + Vec2::new(0, first),
+                        size :   Vec2::new(rect.size.x, second),
+                    },
+                )
+            }
+        };
+        split.first.layout_rec(first_rect, result);
+        split.second.layout_rec(second_rect, result);
+    }
+}
+
+// Distance from one rect to another lying in given direction, None if it doesn't lie there.
+fn gap(from : &PaneRect, to : &PaneRect, direction : PaneDirection) -> Option<usize> {
+    match direction {
+        PaneDirection::Left => from.offset.x.checked_sub(to.offset.x + to.size.x),
+        PaneDirection::Right => to.offset.x.checked_sub(from.offset.x + from.size.x),
+        PaneDirection::Up => from.offset.y.checked_sub(to.offset.y + to.size.y),
+        PaneDirection::Down => to.offset.y.checked_sub(from.offset.y + from.size.y),
+    }
+}
+
+// Length of common part of rects, measured across given direction.
+fn overlap(a : &PaneRect, b : &PaneRect, direction : PaneDirection) -> usize {
+    let (a_begin, a_len, b_begin, b_len) = match direction.orientation() {
+        Orientation::Horizontal => (a.offset.y, a.size.y, b.offset.y, b.size.y),
+        Orientation::Vertical => (a.offset.x, a.size.x, b.offset.x, b.size.x),
+    };
+    cmp::min(a_begin + a_len, b_begin + b_len).saturating_sub(cmp::max(a_begin, b_begin))
+}
+
+impl PaneLayout {
+    pub fn rect(&self, pane : &ViewHandle) -> Option<PaneRect> {
+        self.panes.iter().find(|&&(ref handle, _)| handle == pane).map(|&(_, rect)| rect)
+    }
+
+    /// Returns pane at given position of screen.
+    pub fn pane_at(&self, position : Vec2) -> Option<&ViewHandle> {
+        self.panes.iter().find(|&&(_, rect)| rect.contains(position)).map(|&(ref handle, _)| handle)
+    }
+
+    /// Returns the closest pane lying in given direction from pane, and sharing the most of it's
+    /// border with it.
+    pub fn neighbour(&self, pane : &ViewHandle, direction : PaneDirection) -> Option<ViewHandle> {
+        let from = self.rect(pane)?;
+        self.panes
+            .iter()
+            .filter_map(|&(ref handle, ref rect)| {
+                let common = overlap(&from, rect, direction);
+                gap(&from, rect, direction)
+                    .filter(|_| common > 0)
+                    .map(|distance| (handle, distance, common))
+            })
+            .min_by_key(|&(_, distance, common)| (distance, cmp::Reverse(common)))
+            .map(|(handle, _, _)| handle.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panes_are_split_laid_out_and_closed() {
+        let (a, b, c) = (ViewHandle::new(), ViewHandle::new(), ViewHandle::new());
+        let mut tree = PaneTree::new(a.clone());
+        assert!(tree.split(&a, &b, PaneDirection::Right));
+        assert!(tree.split(&b, &c, PaneDirection::Down));
+        assert_eq!(tree.panes(), vec![a.clone(), b.clone(), c.clone()]);
+
+        let rect = |x, y, width, height| PaneRect {
+            offset : Vec2::new(x, y),
+            size :   Vec2::new(width, height),
+        };
+        let layout = tree.layout(Vec2::new(21, 10));
+        assert_eq!(layout.rect(&a), Some(rect(0, 0, 10, 10)));
+        assert_eq!(layout.rect(&b), Some(rect(11, 0, 10, 5)));
+        assert_eq!(layout.rect(&c), Some(rect(11, 5, 10, 5)));
+        assert_eq!(layout.separators.len(), 1);
+        assert_eq!(layout.pane_at(Vec2::new(12, 7)), Some(&c));
+
+        assert_eq!(layout.neighbour(&a, PaneDirection::Right), Some(b.clone()));
+        assert_eq!(layout.neighbour(&c, PaneDirection::Up), Some(b.clone()));
+        assert_eq!(layout.neighbour(&c, PaneDirection::Left), Some(a.clone()));
+        assert_eq!(layout.neighbour(&a, PaneDirection::Left), None);
+
+        assert!(tree.close(&b));
+        assert_eq!(tree.panes(), vec![a.clone(), c.clone()]);
+        assert!(tree.close(&a));
+        assert!(!tree.close(&c));
+        assert_eq!(tree.panes(), vec![c.clone()]);
+    }
+
+    #[test]
+    fn resize_moves_the_closest_matching_separator() {
+        let (a, b, c) = (ViewHandle::new(), ViewHandle::new(), ViewHandle::new());
+        let mut tree = PaneTree::new(a.clone());
+        tree.split(&a, &b, PaneDirection::Right);
+        tree.split(&b, &c, PaneDirection::Down);
+
+        assert!(tree.resize(&c, PaneDirection::Left, 20));
+        assert_eq!(tree.layout(Vec2::new(21, 10)).rect(&a).unwrap().size, Vec2::new(6, 10));
+
+        assert!(tree.resize(&b, PaneDirection::Down, 100));
+        assert_eq!(tree.layout(Vec2::new(21, 10)).rect(&c).unwrap().size, Vec2::new(14, 1));
+
+        assert!(!tree.resize(&a, PaneDirection::Up, 10));
+    }
+}
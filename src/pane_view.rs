@@ -0,0 +1,232 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Fullscreen view showing text views in panes, arranged by PaneTree. Keyboard events go to the
+// focused pane, mouse ones to the pane under pointer (pressing a button focuses it). While a button
+// is held, the pane it was pressed in keeps getting events, so dragging over a separator does not
+// start a selection in the neighbouring pane. Every pane is a separate SlyTextView, so panes
+// showing the same buffer have their own cursors and scroll.
+
+use core::any::Any;
+use cursive::direction::Direction;
+use cursive::event::{Event, EventResult, MouseEvent};
+use cursive::vec::Vec2;
+use cursive::view::{Selector, View};
+use cursive::views::IdView;
+use cursive::Printer;
+
+use pane_layout::{PaneDirection, PaneLayout, PaneTree};
+use settings::Settings;
+use sly_text_view::SlyTextView;
+use sly_view::SlyView;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::HashMap;
+use std::rc::Rc;
+use view_handle::ViewHandle;
+
+const SEPARATOR : &'static str = "\u{2502}";
+
+pub struct PaneView {
+    tree :     PaneTree,
+    panes :    HashMap<ViewHandle, IdView<SlyTextView>>, // by handle of text view
+    focused :  ViewHandle,
+    dragged :  Option<ViewHandle>, // pane a mouse button was pressed in, until it's released
+    layout :   PaneLayout, // set in layout
+    settings : Rc<RefCell<Settings>>,
+    handle :   ViewHandle,
+}
+
+impl PaneView {
+    pub fn new(editor : IdView<SlyTextView>, settings : Rc<RefCell<Settings>>) -> IdView<Self> {
+        let focused = editor.handle();
+        let mut panes = HashMap::new();
+        panes.insert(focused.clone(), editor);
+
+        let view = PaneView {
+            tree :     PaneTree::new(focused.clone()),
+            panes :    panes,
+            focused :  focused,
+            dragged :  None,
+            layout :   PaneLayout::default(),
+            settings : settings,
+            handle :   ViewHandle::new(),
+        };
+
+        IdView::new(view.handle(), view)
+    }
+
+    /// Returns handle of text view in focused pane.
+    pub fn focused(&self) -> &ViewHandle {
+        &self.focused
+    }
+
+    pub fn num_panes(&self) -> usize {
+        self.panes.len()
+    }
+
+    /// Puts editor in a new pane next to the focused one, and focuses it.
+    pub fn split(&mut self, editor : IdView<SlyTextView>, direction : PaneDirection) {
+        let handle = editor.handle();
+        self.tree.split(&self.focused, &handle, direction);
+        self.panes.insert(handle.clone(), editor);
+        self.focused = handle;
+    }
+
+    /// Closes focused pane, returning it's editor. The last pane can't be closed.
+    pub fn close_focused(&mut self) -> Option<IdView<SlyTextView>> {
+        let order = self.tree.panes();
+        if !self.tree.close(&self.focused) {
+            return None;
+        }
+
+        let idx = order.iter().position(|handle| handle == &self.focused).unwrap();
+        let editor = self.panes.remove(&self.focused);
+        let remaining = self.tree.panes();
+        self.focused = remaining[cmp::min(idx, remaining.len() - 1)].clone();
+        editor
+    }
+
+    /// Shows editor in focused pane instead of the current one, which is returned.
+    pub fn replace_focused(&mut self, editor : IdView<SlyTextView>) -> IdView<SlyTextView> {
+        let handle = editor.handle();
+        self.tree.replace(&self.focused, &handle);
+        let old_editor = self.panes.remove(&self.focused).unwrap();
+        self.panes.insert(handle.clone(), editor);
+        self.focused = handle;
+        old_editor
+    }
+
+    /// Moves focus to the neighbouring pane in given direction, if there is one.
+    pub fn focus(&mut self, direction : PaneDirection) -> bool {
+        match self.layout.neighbour(&self.focused, direction) {
+            Some(handle) => {
+                self.focused = handle;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Calls f with editor of every pane.
+    pub fn for_each_editor<F : FnMut(&mut SlyTextView)>(&mut self, mut f : F) {
+        for pane in self.panes.values_mut() {
+            pane.with_view_mut(|editor| f(editor));
+        }
+    }
+
+    /// Moves separator of focused pane in given direction, by step percent of it's split.
+    pub fn resize(&mut self, direction : PaneDirection, step : usize) -> bool {
+        self.tree.resize(&self.focused, direction, step)
+    }
+}
+
+impl View for PaneView {
+    fn draw(&self, printer : &Printer) {
+        let style = self.settings.borrow().get_colorstyle(
+            "theme/text_view/secondary_text_color",
+            "theme/text_view/background_color",
+        );
+        for separator in &self.layout.separators {
+            printer.with_color(style, |printer| {
+                printer.print_vline(separator.offset, separator.size.y, SEPARATOR);
+            });
+        }
+
+        for &(ref handle, ref rect) in &self.layout.panes {
+            let pane_printer =
+                printer.offset(rect.offset).cropped(rect.size).focused(handle == &self.focused);
+            self.panes[handle].draw(&pane_printer);
+        }
+    }
+
+    fn required_size(&mut self, constraint : Vec2) -> Vec2 {
+        constraint
+    }
+
+    fn layout(&mut self, size : Vec2) {
+        self.layout = self.tree.layout(size);
+        for &(ref handle, ref rect) in &self.layout.panes {
+            self.panes.get_mut(handle).unwrap().layout(rect.size);
+        }
+    }
+
+    fn on_event(&mut self, event : Event) -> EventResult {
+        let handle = match event {
+            Event::Mouse { event : mouse_event, offset, position } => {
+                let under_pointer = position
+                    .checked_sub(offset)
+                    .and_then(|position| self.layout.pane_at(position))
+                    .cloned();
+                let handle_op = match mouse_event {
+                    MouseEvent::Hold(_) | MouseEvent::Release(_) if self.dragged.is_some() => {
+                        self.dragged.clone()
+                    }
+                    _ => under_pointer,
+                };
+
+                match mouse_event {
+                    MouseEvent::Press(_) => {
+                        self.dragged = handle_op.clone();
+                        if let Some(ref handle) = handle_op {
+                            self.focused = handle.clone();
+                        }
+                    }
+                    MouseEvent::Release(_) => self.dragged = None,
+                    _ => {}
+                }
+
+                match handle_op {
+                    Some(handle) => handle,
+                    None => return EventResult::Ignored, // separator
+                }
+            }
+            _ => self.focused.clone(),
+        };
+
+        let offset = match self.layout.rect(&handle) {
+            Some(rect) => rect.offset,
+            None => return EventResult::Ignored, // not laid out yet
+        };
+        self.panes.get_mut(&handle).unwrap().on_event(event.relativized(offset))
+    }
+
+    fn call_on_any<'a>(&mut self, selector : &Selector, mut callback : Box<FnMut(&mut Any) + 'a>) {
+        for pane in self.panes.values_mut() {
+            pane.call_on_any(selector, Box::new(|any| callback(any)));
+        }
+    }
+
+    fn focus_view(&mut self, selector : &Selector) -> Result<(), ()> {
+        for (handle, pane) in self.panes.iter_mut() {
+            if pane.focus_view(selector).is_ok() {
+                self.focused = handle.clone();
+                return Ok(());
+            }
+        }
+        Err(())
+    }
+
+    fn take_focus(&mut self, _source : Direction) -> bool {
+        true
+    }
+}
+
+impl SlyView for PaneView {
+    fn handle(&self) -> ViewHandle {
+        self.handle.clone()
+    }
+}
@@ -0,0 +1,347 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Preview of project wide replace. Lists files with their hunks, every hunk as a pair of lines:
+// before and after the replacement. Files and hunks can be deselected (space), enter applies
+// selected ones, esc cancels. Nothing is written to disk: Interface applies result to buffers.
+
+use cursive::event::{Event, EventResult, Key};
+use cursive::theme::ColorStyle;
+use cursive::vec::Vec2;
+use cursive::view::View;
+use cursive::views::IdView;
+use cursive::Printer;
+
+use overlay_dialog::OverlayDialog;
+use project_search::FileReplacements;
+use settings::Settings;
+use sly_view::SlyView;
+use std::cell::{Ref, RefCell};
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::rc::Rc;
+use unicode_width::UnicodeWidthStr;
+use view_handle::ViewHandle;
+
+const WIDTH : usize = 120;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Entry {
+    File(usize),
+    Hunk(usize, usize), // file idx, hunk idx
+}
+
+pub struct ProjectReplaceView {
+    files :    Vec<FileReplacements>,
+    entries :  Vec<Entry>,
+    selected : usize, // idx of entry
+    scroll :   usize, // first visible row
+    size :     Option<Vec2>,
+    settings : Rc<RefCell<Settings>>,
+    handle :   ViewHandle,
+    result :   Option<Result<ProjectReplaceResult, ProjectReplaceError>>,
+}
+
+impl ProjectReplaceView {
+    pub fn new(files : Vec<FileReplacements>, settings : Rc<RefCell<Settings>>) -> IdView<Self> {
+        let mut entries : Vec<Entry> = Vec::new();
+        for (file_idx, file) in files.iter().enumerate() {
+            entries.push(Entry::File(file_idx));
+            for hunk_idx in 0..file.hunks.len() {
+                entries.push(Entry::Hunk(file_idx, hunk_idx));
+            }
+        }
+
+        let view = ProjectReplaceView {
+            files :    files,
+            entries :  entries,
+            selected : 0,
+            scroll :   0,
+            size :     None,
+            settings : settings,
+            handle :   ViewHandle::new(),
+            result :   None,
+        };
+
+        IdView::new(view.handle(), view)
+    }
+
+    fn settings_ref(&self) -> Ref<Settings> {
+        self.settings.borrow()
+    }
+
+    fn entry_height(entry : &Entry) -> usize {
+        match entry {
+            &Entry::File(_) => 1,
+            &Entry::Hunk(_, _) => 2,
+        }
+    }
+
+    // First row of entry, counted from the first entry.
+    fn entry_row(&self, entry_idx : usize) -> usize {
+        self.entries[..entry_idx].iter().map(Self::entry_height).sum()
+    }
+
+    fn num_rows(&self) -> usize {
+        self.entry_row(self.entries.len())
+    }
+
+    // Rows available for entries, first row being the header.
+    fn list_height(&self) -> usize {
+        self.size.map(|size| size.y).unwrap_or(1).saturating_sub(1)
+    }
+
+    fn make_sure_selected_visible(&mut self) {
+        let height = self.list_height();
+        let begin = self.entry_row(self.selected);
+        let end = begin + Self::entry_height(&self.entries[self.selected]);
+        if end > self.scroll + height {
+            self.scroll = end.saturating_sub(height);
+        }
+        if begin < self.scroll {
+            self.scroll = begin;
+        }
+    }
+
+    fn num_selected(&self) -> usize {
+        self.files.iter().map(|file| file.num_selected()).sum()
+    }
+
+    fn toggle_selected(&mut self) {
+        match self.entries[self.selected] {
+            Entry::File(file_idx) => {
+                let file = &mut self.files[file_idx];
+                let select = file.num_selected() == 0;
+                for hunk in file.hunks.iter_mut() {
+                    hunk.selected = select;
+                }
+            }
+            Entry::Hunk(file_idx, hunk_idx) => {
+                let hunk = &mut self.files[file_idx].hunks[hunk_idx];
+                hunk.selected = !hunk.selected;
+            }
+        }
+    }
+
+    fn apply(&mut self) {
+        let files : Vec<FileReplacements> =
+            self.files.iter().filter(|file| file.num_selected() > 0).cloned().collect();
+        self.result = Some(Ok(ProjectReplaceResult::Apply(files)));
+    }
+
+    fn colorstyle(&self, selected : bool, secondary : bool) -> ColorStyle {
+        self.settings_ref().get_colorstyle(
+            if secondary {
+                "theme/fuzzy_view/secondary_text_color"
+            } else {
+                "theme/fuzzy_view/primary_text_color"
+            },
+            if selected {
+                "theme/fuzzy_view/selected_background_color"
+            } else {
+                "theme/fuzzy_view/background_color"
+            },
+        )
+    }
+
+    // Returns text of given row of entry.
+    fn entry_row_text(&self, entry : &Entry, row : usize) -> String {
+        let mark = |selected : bool| if selected { "[x]" } else { "[ ]" };
+        match entry {
+            &Entry::File(file_idx) => {
+                let file = &self.files[file_idx];
+                format!(
+                    "{} {} ({} of {})",
+                    mark(file.num_selected() > 0),
+                    file.path.to_string_lossy(),
+                    file.num_selected(),
+                    file.hunks.len()
+                )
+            }
+            &Entry::Hunk(file_idx, hunk_idx) => {
+                let hunk = &self.files[file_idx].hunks[hunk_idx];
+                let line_desc = (hunk.line + 1).to_string();
+                let text = if row == 0 { &hunk.before } else { &hunk.after };
+                format!(
+                    "    {} {:>5}{} {}",
+                    if row == 0 { mark(hunk.selected) } else { "   " },
+                    if row == 0 { line_desc.as_str() } else { "" },
+                    if row == 0 { "-" } else { "+" },
+                    text.replace('\n', "\u{21B5}")
+                )
+            }
+        }
+    }
+}
+
+impl View for ProjectReplaceView {
+    fn draw(&self, printer : &Printer) {
+        let width = self.size.map(|size| size.x).unwrap_or(0);
+
+        let header = format!(
+            " replace: {} change(s) in {} file(s). space - toggle, enter - apply, esc - cancel",
+            self.num_selected(),
+            self.files.iter().filter(|file| file.num_selected() > 0).count()
+        );
+        printer.with_color(self.colorstyle(false, false), |printer| {
+            printer.print_hline((0, 0), width, " ");
+            printer.print((0, 0), &header);
+        });
+
+        let height = self.list_height();
+        let mut row : usize = 0;
+        for (entry_idx, entry) in self.entries.iter().enumerate() {
+            for entry_row in 0..Self::entry_height(entry) {
+                if row >= self.scroll && row < self.scroll + height {
+                    let y = row - self.scroll + 1;
+                    let secondary = match entry {
+                        &Entry::Hunk(file_idx, hunk_idx) => {
+                            !self.files[file_idx].hunks[hunk_idx].selected
+                        }
+                        &Entry::File(file_idx) => self.files[file_idx].num_selected() == 0,
+                    };
+                    let style = self.colorstyle(entry_idx == self.selected, secondary);
+                    let text = self.entry_row_text(entry, entry_row);
+                    printer.with_color(style, |printer| {
+                        printer.print_hline((0, y), width, " ");
+                        printer.print((0, y), &text);
+                    });
+                }
+                row += 1;
+            }
+        }
+    }
+
+    fn required_size(&mut self, constraint : Vec2) -> Vec2 {
+        let widest = (0..self.entries.len())
+            .flat_map(|idx| {
+                let entry = self.entries[idx];
+                (0..Self::entry_height(&entry)).map(move |row| (entry, row))
+            })
+            .map(|(entry, row)| self.entry_row_text(&entry, row).width())
+            .max()
+            .unwrap_or(0);
+        Vec2::new(
+            cmp::min(cmp::min(cmp::max(widest, WIDTH / 2), WIDTH), constraint.x),
+            cmp::min(1 + self.num_rows(), constraint.y),
+        )
+    }
+
+    fn layout(&mut self, size : Vec2) {
+        self.size = Some(size);
+        if !self.entries.is_empty() {
+            self.make_sure_selected_visible();
+        }
+    }
+
+    fn on_event(&mut self, event : Event) -> EventResult {
+        if self.entries.is_empty() {
+            if event == Event::Key(Key::Esc) {
+                self.cancel();
+                return EventResult::Consumed(None);
+            }
+            return EventResult::Ignored;
+        }
+
+        match event {
+            Event::Key(Key::Up) => {
+                if self.selected > 0 {
+                    self.selected -= 1;
+                }
+            }
+            Event::Key(Key::Down) => {
+                if self.selected + 1 < self.entries.len() {
+                    self.selected += 1;
+                }
+            }
+            Event::Key(Key::PageUp) => {
+                self.selected -= cmp::min(self.selected, cmp::max(self.list_height() / 2, 1));
+            }
+            Event::Key(Key::PageDown) => {
+                self.selected = cmp::min(
+                    self.entries.len() - 1,
+                    self.selected + cmp::max(self.list_height() / 2, 1),
+                );
+            }
+            Event::Char(' ') => {
+                self.toggle_selected();
+            }
+            Event::Key(Key::Enter) => {
+                self.apply();
+            }
+            Event::Key(Key::Esc) => {
+                self.cancel();
+            }
+            _ => {
+                debug!("project replace view got unhandled event {:?}", &event);
+                return EventResult::Ignored;
+            }
+        }
+
+        self.make_sure_selected_visible();
+        EventResult::Consumed(None)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ProjectReplaceResult {
+    Cancel,
+    Apply(Vec<FileReplacements>), // only files with selected hunks
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProjectReplaceError;
+
+impl fmt::Display for ProjectReplaceError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ProjectReplaceError (not defined)")
+    }
+}
+
+impl std::error::Error for ProjectReplaceError {
+    fn description(&self) -> &str {
+        "ProjectReplaceError (not defined)"
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        None
+    }
+}
+
+impl OverlayDialog<ProjectReplaceResult, ProjectReplaceError> for ProjectReplaceView {
+    fn is_displayed(&self) -> bool {
+        self.result.is_none()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.result.is_some()
+    }
+
+    fn get_result(&self) -> Option<Result<ProjectReplaceResult, ProjectReplaceError>> {
+        self.result.clone()
+    }
+
+    fn cancel(&mut self) {
+        self.result = Some(Ok(ProjectReplaceResult::Cancel))
+    }
+}
+
+impl SlyView for ProjectReplaceView {
+    fn handle(&self) -> ViewHandle {
+        self.handle.clone()
+    }
+}
@@ -0,0 +1,467 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Find in files. ProjectSearchIndex is a FuzzyIndexTrait, so results can be browsed in
+// FuzzyQueryView, but instead of fuzzy matching headers it greps contents of every file of the
+// file index (the one built by AppState, so it respects .gitignore).
+// Files are read by a pool of worker threads, matches are sent back over a channel as they are
+// found and collected whenever FuzzyQueryView asks for results. Changing the query drops the old
+// task, which makes it's workers stop at the next file.
+// Project wide replace is not streamed: all replacements are computed up front (from open buffers
+// where there are any, from disk otherwise), so they can be previewed and confirmed as a whole.
+// Search results can be also opened as an editable buffer (see BufferKind::SearchResults), with
+// one "path:line: text" row per match. Rows edited there are written back on save.
+
+use fuzzy_index_trait::FuzzyIndexTrait;
+use fuzzy_view_item::ViewItem;
+use interface::InterfaceNotifier;
+use regex::Regex;
+use ropey::Rope;
+use search::{build_regex, replacements, SearchOptions};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use text_navigation::line_end_without_newline;
+use std::sync::mpsc;
+use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+use std::thread;
+use text_navigation::line_end_without_newline;
+
+const NUM_WORKERS : usize = 4;
+const MAX_RESULTS : usize = 1000;
+const MAX_PREVIEW_LEN : usize = 120;
+
+/// Single line matching the query. Line and column (in chars) are 0 based.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectMatch {
+    pub path :   PathBuf,
+    pub line :   usize,
+    pub column : usize,
+    pub text :   String,
+}
+
+impl ProjectMatch {
+    /// Marker is "line:column:path", so it can be parsed back with parse_marker.
+    pub fn to_item(&self) -> ViewItem {
+        let preview : String = self.text.trim().chars().take(MAX_PREVIEW_LEN).collect();
+        ViewItem::new(
+            format!("{}:{}: {}", self.path.to_string_lossy(), self.line + 1, preview),
+            None,
+            format!("{}:{}:{}", self.line, self.column, self.path.to_string_lossy()),
+        )
+    }
+}
+
+/// Parses item marker into (path, line, column).
+pub fn parse_marker(marker : &str) -> Option<(PathBuf, usize, usize)> {
+    let mut parts = marker.splitn(3, ':');
+    let line = parts.next()?.parse::<usize>().ok()?;
+    let column = parts.next()?.parse::<usize>().ok()?;
+    let path = parts.next()?;
+    Some((PathBuf::from(path), line, column))
+}
+
+/// Returns a match for every line of text regex matches, with column of first match in it. Lines
+/// are split by rope, so their numbers are the ones used when rows are written back.
+pub fn search_text(path : &PathBuf, rope : &Rope, regex : &Regex) -> Vec<ProjectMatch> {
+    let mut result : Vec<ProjectMatch> = Vec::new();
+    for line_idx in 0..rope.len_lines() {
+        let line_begin = rope.line_to_char(line_idx);
+        let line = rope.slice(line_begin..line_end_without_newline(rope, line_idx)).to_string();
+        if let Some(m) = regex.find_iter(&line).find(|m| m.start() != m.end()) {
+            result.push(ProjectMatch {
+                path :   path.clone(),
+                line :   line_idx,
+                column : line[..m.start()].chars().count(),
+                text :   line.clone(),
+            });
+        }
+    }
+    result
+}
+
+/// Text of search results buffer, one "path:line: text" row per match, line being 1 based.
+pub fn results_to_text(matches : &Vec<ProjectMatch>) -> String {
+    let mut result = String::new();
+    for m in matches {
+        result.push_str(&format!("{}:{}: {}\n", m.path.to_string_lossy(), m.line + 1, m.text));
+    }
+    result
+}
+
+// Parses row of search results buffer into (path, line (0 based), text). Paths can contain ':',
+// so path has to be one of known ones.
+fn parse_result_row<'a>(
+    row : &'a str,
+    known_paths : &HashSet<String>,
+) -> Option<(&'a str, usize, &'a str)> {
+    for (idx, _) in row.match_indices(':') {
+        let path = &row[..idx];
+        if !known_paths.contains(path) {
+            continue;
+        }
+
+        let rest = &row[idx + 1..];
+        let separator = rest.find(": ")?;
+        let line = rest[..separator].parse::<usize>().ok()?;
+        if line == 0 {
+            return None;
+        }
+        return Some((path, line - 1, &rest[separator + 2..]));
+    }
+    None
+}
+
+/// Compares search results buffer text with matches it was created from. Returns rows that were
+/// modified, as (original match, new text) pairs. Rows that were removed or can no longer be
+/// parsed are ignored, so is every but first row pointing at the same line.
+pub fn changed_results(text : &str, matches : &Vec<ProjectMatch>) -> Vec<(ProjectMatch, String)> {
+    let known_paths : HashSet<String> =
+        matches.iter().map(|m| m.path.to_string_lossy().to_string()).collect();
+    let mut originals : HashMap<(String, usize), &ProjectMatch> = HashMap::new();
+    for m in matches {
+        originals.insert((m.path.to_string_lossy().to_string(), m.line), m);
+    }
+
+    let mut result : Vec<(ProjectMatch, String)> = Vec::new();
+    for row in text.lines() {
+        if let Some((path, line, new_text)) = parse_result_row(row, &known_paths) {
+            // removing, so only the first row pointing at given line counts.
+            if let Some(original) = originals.remove(&(path.to_string(), line)) {
+                if original.text != new_text {
+                    result.push((original.clone(), new_text.to_string()));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Single replacement, along with the lines it touches before and after it's applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub range :       Range<usize>, // in chars
+    pub line :        usize,
+    pub matched :     String,
+    pub replacement : String,
+    pub before :      String,
+    pub after :       String,
+    pub selected :    bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReplacements {
+    pub path :  PathBuf,
+    pub hunks : Vec<Hunk>, // sorted by range
+}
+
+impl FileReplacements {
+    pub fn num_selected(&self) -> usize {
+        self.hunks.iter().filter(|hunk| hunk.selected).count()
+    }
+
+    /// Selected replacements, in format accepted by search::replacement_events.
+    pub fn selected_replacements(&self) -> Vec<(Range<usize>, String)> {
+        self.hunks
+            .iter()
+            .filter(|hunk| hunk.selected)
+            .map(|hunk| (hunk.range.clone(), hunk.replacement.clone()))
+            .collect()
+    }
+
+    /// Checks whether rope still has matched text where it was found.
+    pub fn matches_content(&self, rope : &Rope) -> bool {
+        self.hunks.iter().all(|hunk| {
+            hunk.range.end <= rope.len_chars()
+                && rope.slice(hunk.range.clone()).to_string() == hunk.matched
+        })
+    }
+}
+
+/// Computes all replacements of regex in rope. Returns None if there are none.
+pub fn file_replacements(
+    path : &PathBuf,
+    rope : &Rope,
+    regex : &Regex,
+    replacement : &str,
+    expand : bool,
+) -> Option<FileReplacements> {
+    let hunks : Vec<Hunk> = replacements(rope, regex, replacement, expand, |_| true)
+        .into_iter()
+        .map(|(range, new_text)| {
+            let first_line = rope.char_to_line(range.start);
+            let last_line = rope.char_to_line(range.end);
+            let lines_begin = rope.line_to_char(first_line);
+            let lines_end = line_end_without_newline(rope, last_line);
+
+            let prefix = rope.slice(lines_begin..range.start).to_string();
+            let suffix = rope.slice(range.end..cmp::max(range.end, lines_end)).to_string();
+            Hunk {
+                line :        first_line,
+                matched :     rope.slice(range.clone()).to_string(),
+                before :      rope.slice(lines_begin..cmp::max(range.end, lines_end)).to_string(),
+                after :       format!("{}{}{}", prefix, new_text, suffix),
+                replacement : new_text,
+                range :       range,
+                selected :    true,
+            }
+        })
+        .collect();
+
+    if hunks.is_empty() {
+        None
+    } else {
+        Some(FileReplacements { path : path.clone(), hunks : hunks })
+    }
+}
+
+pub struct ProjectSearchIndex {
+    files : Arc<Vec<PathBuf>>,
+    task :  Option<ProjectSearchTask>,
+}
+
+impl ProjectSearchIndex {
+    pub fn new(files : Arc<Vec<PathBuf>>) -> Self {
+        ProjectSearchIndex { files : files, task : None }
+    }
+}
+
+impl FuzzyIndexTrait for ProjectSearchIndex {
+    fn get_results_for(
+        &mut self,
+        query : &String,
+        limit_op : Option<usize>,
+        inot_op : Option<InterfaceNotifier>,
+    ) -> Vec<Rc<ViewItem>> {
+        if query.is_empty() {
+            self.task = None;
+            return Vec::new();
+        }
+
+        let restart = match self.task {
+            Some(ref task) => task.query != *query,
+            None => true,
+        };
+        if restart {
+            // dropping the old task first, so it's workers stop before new ones start.
+            self.task = None;
+            self.task = ProjectSearchTask::new(query.clone(), self.files.clone(), inot_op);
+        }
+
+        match self.task {
+            Some(ref mut task) => {
+                task.poll();
+                let items = &task.items;
+                match limit_op {
+                    Some(limit) if limit < items.len() => items[..limit].to_vec(),
+                    _ => items.clone(),
+                }
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+struct ProjectSearchTask {
+    query :     String,
+    receiver :  mpsc::Receiver<ProjectMatch>,
+    items :     Vec<Rc<ViewItem>>,
+    done :      bool,
+    cancelled : Arc<AtomicBool>,
+}
+
+impl ProjectSearchTask {
+    fn new(
+        query : String,
+        files : Arc<Vec<PathBuf>>,
+        inot_op : Option<InterfaceNotifier>,
+    ) -> Option<Self> {
+        let regex = match build_regex(&query, &SearchOptions::default()) {
+            Ok(regex) => regex,
+            Err(e) => {
+                error!("unable to build regex for query \"{}\": {}", query, e);
+                return None;
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel::<ProjectMatch>();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        // workers take files one by one, so a single big file does not hold the others back.
+        let next_file = Arc::new(AtomicUsize::new(0));
+        let num_found = Arc::new(AtomicUsize::new(0));
+
+        for worker_idx in 0..NUM_WORKERS {
+            let sender = sender.clone();
+            let files = files.clone();
+            let regex = regex.clone();
+            let cancelled = cancelled.clone();
+            let next_file = next_file.clone();
+            let num_found = num_found.clone();
+            let inot_op = inot_op.clone();
+
+            thread::spawn(move || {
+                loop {
+                    if cancelled.load(Ordering::Relaxed)
+                        || num_found.load(Ordering::Relaxed) >= MAX_RESULTS
+                    {
+                        break;
+                    }
+
+                    let file_idx = next_file.fetch_add(1, Ordering::Relaxed);
+                    if file_idx >= files.len() {
+                        break;
+                    }
+
+                    let path = &files[file_idx];
+                    let rope = match fs::File::open(path).and_then(Rope::from_reader) {
+                        Ok(rope) => rope,
+                        Err(e) => {
+                            // most likely a binary file.
+                            debug!("project search: skipping {:?}, because \"{}\"", path, e);
+                            continue;
+                        }
+                    };
+
+                    let matches = search_text(path, &rope, &regex);
+                    if matches.is_empty() {
+                        continue;
+                    }
+
+                    num_found.fetch_add(matches.len(), Ordering::Relaxed);
+                    for m in matches {
+                        if sender.send(m).is_err() {
+                            return; // task was dropped.
+                        }
+                    }
+
+                    if let Some(ref inot) = inot_op {
+                        inot.refresh();
+                    }
+                }
+                debug!("project search worker {}: finished", worker_idx);
+            });
+        }
+
+        Some(ProjectSearchTask {
+            query :     query,
+            receiver :  receiver,
+            items :     Vec::new(),
+            done :      false,
+            cancelled : cancelled,
+        })
+    }
+
+    fn poll(&mut self) {
+        while !self.done && self.items.len() < MAX_RESULTS {
+            match self.receiver.try_recv() {
+                Ok(m) => self.items.push(Rc::new(m.to_item())),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    debug!("project search for \"{}\" done", self.query);
+                    self.done = true;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ProjectSearchTask {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_lines_and_round_trips_markers() {
+        let path = PathBuf::from("src/a:b.rs");
+        let regex = build_regex("Foo", &SearchOptions::default()).unwrap();
+        let rope = Rope::from_str("let x;\nłódź foo();\nfoo foo\n");
+        let matches = search_text(&path, &rope, &regex);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].line, matches[0].column), (1, 5));
+        assert_eq!((matches[1].line, matches[1].column), (2, 0));
+
+        let item = matches[0].to_item();
+        assert_eq!(item.get_header(), "src/a:b.rs:2: łódź foo();");
+        assert_eq!(parse_marker(item.get_marker()), Some((path, 1, 5)));
+    }
+
+    #[test]
+    fn only_modified_result_rows_are_written_back() {
+        let m = |path : &str, line : usize, text : &str| ProjectMatch {
+            path :   PathBuf::from(path),
+            line :   line,
+            column : 0,
+            text :   text.to_string(),
+        };
+        let matches = vec![m("a:1.rs", 0, "x"), m("a:1.rs", 4, "y"), m("b.rs", 9, "  z")];
+        let text = results_to_text(&matches);
+        assert_eq!(text, "a:1.rs:1: x\na:1.rs:5: y\nb.rs:10:   z\n");
+        assert!(changed_results(&text, &matches).is_empty());
+
+        let edited = "a:1.rs:1: x\na:1.rs:5: yy\nb.rs:10:   w\nb.rs:10: again\ngarbage\n";
+        let changed = changed_results(edited, &matches);
+        assert_eq!(changed.len(), 2);
+        assert_eq!((changed[0].0.line, changed[0].1.as_str()), (4, "yy"));
+        assert_eq!((changed[1].0.line, changed[1].1.as_str()), (9, "  w"));
+    }
+
+    // Lone '\r' breaks line in rope, so it has to in search as well.
+    #[test]
+    fn lines_are_numbered_like_in_rope() {
+        let path = PathBuf::from("a.rs");
+        let regex = build_regex("foo", &SearchOptions::default()).unwrap();
+        let matches = search_text(&path, &Rope::from_str("a\rfoo\r\nb\u{2028}foo\n"), &regex);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].line, matches[0].text.as_str()), (1, "foo"));
+        assert_eq!((matches[1].line, matches[1].text.as_str()), (3, "foo"));
+    }
+
+    #[test]
+    fn replacement_hunks_show_whole_lines() {
+        let path = PathBuf::from("a.rs");
+        let rope = Rope::from_str("let foo = 1;\nfoo(foo);\n");
+        let regex = build_regex("f(o+)", &SearchOptions { regex : true, ..Default::default() })
+            .unwrap();
+        let file = file_replacements(&path, &rope, &regex, "b$1", true).unwrap();
+
+        assert_eq!(file.hunks.len(), 3);
+        assert_eq!(file.hunks[0].before, "let foo = 1;");
+        assert_eq!(file.hunks[0].after, "let boo = 1;");
+        assert_eq!(file.hunks[2].line, 1);
+        assert_eq!(file.hunks[2].after, "foo(boo);");
+
+        let mut file = file;
+        file.hunks[1].selected = false;
+        assert_eq!(
+            file.selected_replacements(),
+            vec![(4..7, "boo".to_string()), (17..20, "boo".to_string())]
+        );
+        assert!(file.matches_content(&rope));
+        assert!(!file.matches_content(&Rope::from_str("let bar")));
+    }
+}
@@ -112,6 +112,10 @@ impl HighlightSettings {
             syntax_set : syntax_set,
         })
     }
+
+    pub fn syntax_name(&self) -> &str {
+        &self.syntax.name
+    }
 }
 
 #[derive(Clone, Debug)]
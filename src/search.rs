@@ -0,0 +1,426 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// In-buffer search and replace. FindBar keeps the state of search of a single SlyTextView: query,
+// options, replacement and matches, that are recomputed whenever query, options or the buffer
+// revision change.
+// regex crate needs contiguous text, so Rope is converted to String for every search. Results
+// are in CHARS.
+
+use content_provider::{EditEvent, RevisionId};
+use cursor::{map_cursors, Cursor};
+use regex;
+use regex::{Regex, RegexBuilder};
+use ropey::Rope;
+use std::ops::Range;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchOptions {
+    pub case_sensitive : bool,
+    pub whole_word :     bool,
+    pub regex :          bool,
+}
+
+/// Builds regex for query. Unless options.regex is set, query is matched literally.
+pub fn build_regex(query : &str, options : &SearchOptions) -> Result<Regex, regex::Error> {
+    let mut pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+    if options.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+
+    RegexBuilder::new(&pattern).case_insensitive(!options.case_sensitive).multi_line(true).build()
+}
+
+/// Returns char ranges of all non-empty matches of regex in rope.
+pub fn find_all(rope : &Rope, regex : &Regex) -> Vec<Range<usize>> {
+    let text = rope.to_string();
+    regex
+        .find_iter(&text)
+        .filter(|m| m.start() != m.end())
+        .map(|m| rope.byte_to_char(m.start())..rope.byte_to_char(m.end()))
+        .collect()
+}
+
+/// Computes replacement text for every non-empty match of regex in rope, accepted by filter.
+/// With expand set, "$1" and "${name}" in replacement are substituted with capture groups.
+/// Returns char ranges of matches along with their replacements.
+pub fn replacements<F>(
+    rope : &Rope,
+    regex : &Regex,
+    replacement : &str,
+    expand : bool,
+    filter : F,
+) -> Vec<(Range<usize>, String)>
+where
+    F : Fn(&Range<usize>) -> bool,
+{
+    let text = rope.to_string();
+    let mut result : Vec<(Range<usize>, String)> = Vec::new();
+    for captures in regex.captures_iter(&text) {
+        let whole = captures.get(0).unwrap();
+        if whole.start() == whole.end() {
+            continue;
+        }
+
+        let range = rope.byte_to_char(whole.start())..rope.byte_to_char(whole.end());
+        if !filter(&range) {
+            continue;
+        }
+
+        let new_text = if expand {
+            let mut new_text = String::new();
+            captures.expand(replacement, &mut new_text);
+            new_text
+        } else {
+            replacement.to_string()
+        };
+        result.push((range, new_text));
+    }
+    result
+}
+
+/// Turns replacements into events, applied from the last one so offsets stay valid.
+pub fn replacement_events(replacements : Vec<(Range<usize>, String)>) -> Vec<EditEvent> {
+    replacements
+        .into_iter()
+        .rev()
+        .map(|(range, new_text)| EditEvent::Change {
+            offset :  range.start,
+            length :  range.end - range.start,
+            content : new_text,
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FindBarField {
+    Query,
+    Replacement,
+}
+
+pub struct FindBar {
+    query :   String,
+    options : SearchOptions,
+    matches : Vec<Range<usize>>, // sorted
+    error :   Option<String>,
+    /// Some if bar is in replace mode.
+    replacement : Option<String>,
+    focus :       FindBarField,
+    /// Selections search is limited to, None if whole buffer is searched. Kept as cursors, so
+    /// they can be mapped through edits.
+    scope : Option<Vec<Cursor>>,
+    /// Offset the incremental search starts from, set when bar is opened.
+    origin : usize,
+    /// Revision of buffer matches were computed for. None means they have to be recomputed.
+    searched_revision : Option<RevisionId>,
+}
+
+impl FindBar {
+    pub fn new(query : String, origin : usize) -> Self {
+        FindBar {
+            query :             query,
+            options :           SearchOptions::default(),
+            matches :           Vec::new(),
+            error :             None,
+            replacement :       None,
+            focus :             FindBarField::Query,
+            scope :             None,
+            origin :            origin,
+            searched_revision : None,
+        }
+    }
+
+    pub fn query(&self) -> &String {
+        &self.query
+    }
+
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    pub fn origin(&self) -> usize {
+        self.origin
+    }
+
+    /// Switches bar to replace mode, focusing replacement field.
+    pub fn enable_replace(&mut self) {
+        if self.replacement.is_none() {
+            self.replacement = Some(String::new());
+        }
+        self.focus = FindBarField::Replacement;
+    }
+
+    pub fn replacement(&self) -> Option<&String> {
+        self.replacement.as_ref()
+    }
+
+    pub fn focus(&self) -> FindBarField {
+        self.focus
+    }
+
+    pub fn switch_field(&mut self) {
+        if self.replacement.is_some() {
+            self.focus = match self.focus {
+                FindBarField::Query => FindBarField::Replacement,
+                FindBarField::Replacement => FindBarField::Query,
+            };
+        }
+    }
+
+    /// Number of rows the bar takes.
+    pub fn height(&self) -> usize {
+        if self.replacement.is_some() {
+            2
+        } else {
+            1
+        }
+    }
+
+    // Adds char to focused field.
+    pub fn push_char(&mut self, c : char) {
+        match (self.focus, self.replacement.as_mut()) {
+            (FindBarField::Replacement, Some(replacement)) => replacement.push(c),
+            _ => {
+                self.query.push(c);
+                self.searched_revision = None;
+            }
+        }
+    }
+
+    // Removes last char of focused field.
+    pub fn pop_char(&mut self) {
+        match (self.focus, self.replacement.as_mut()) {
+            (FindBarField::Replacement, Some(replacement)) => {
+                replacement.pop();
+            }
+            _ => {
+                self.query.pop();
+                self.searched_revision = None;
+            }
+        }
+    }
+
+    pub fn scope(&self) -> Option<&Vec<Cursor>> {
+        self.scope.as_ref()
+    }
+
+    /// Limits search to selections of given cursors (cursors without selection are ignored), or
+    /// removes the limit if None or there are no selections.
+    pub fn set_scope(&mut self, cursors_op : Option<Vec<Cursor>>) {
+        self.scope = cursors_op
+            .map(|cursors| cursors.into_iter().filter(|c| c.has_selection()).collect::<Vec<_>>())
+            .and_then(|cursors| if cursors.is_empty() { None } else { Some(cursors) });
+        self.searched_revision = None;
+    }
+
+    /// Keeps scope in place after events were applied to buffer.
+    pub fn map_scope(&mut self, events : &Vec<EditEvent>) {
+        if let Some(ref mut scope) = self.scope {
+            map_cursors(scope, events);
+        }
+    }
+
+    fn in_scope(&self, range : &Range<usize>) -> bool {
+        match self.scope {
+            None => true,
+            Some(ref scope) => {
+                scope.iter().any(|c| c.begin() <= range.start && range.end <= c.end())
+            }
+        }
+    }
+
+    /// Returns replacements for all matches (within scope), if bar is in replace mode.
+    pub fn all_replacements(&self, rope : &Rope) -> Vec<(Range<usize>, String)> {
+        match (self.regex(), self.replacement.as_ref()) {
+            (Some(regex), Some(replacement)) => {
+                replacements(rope, &regex, replacement, self.options.regex, |range| {
+                    self.in_scope(range)
+                })
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns replacement for a single match, if bar is in replace mode and range is a match.
+    pub fn replacement_for(&self, rope : &Rope, range : &Range<usize>) -> Option<String> {
+        match (self.regex(), self.replacement.as_ref()) {
+            (Some(regex), Some(replacement)) => {
+                replacements(rope, &regex, replacement, self.options.regex, |r| r == range)
+                    .pop()
+                    .map(|(_, new_text)| new_text)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn toggle_case_sensitive(&mut self) {
+        self.options.case_sensitive = !self.options.case_sensitive;
+        self.searched_revision = None;
+    }
+
+    pub fn toggle_whole_word(&mut self) {
+        self.options.whole_word = !self.options.whole_word;
+        self.searched_revision = None;
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.options.regex = !self.options.regex;
+        self.searched_revision = None;
+    }
+
+    /// Returns regex for current query and options, None if query is empty or invalid.
+    pub fn regex(&self) -> Option<Regex> {
+        if self.query.is_empty() {
+            None
+        } else {
+            build_regex(&self.query, &self.options).ok()
+        }
+    }
+
+    /// Recomputes matches, unless they are up to date with given revision of buffer.
+    pub fn refresh(&mut self, rope : &Rope, revision : RevisionId) {
+        if self.searched_revision == Some(revision) {
+            return;
+        }
+        self.searched_revision = Some(revision);
+        self.matches.clear();
+        self.error = None;
+
+        if self.query.is_empty() {
+            return;
+        }
+
+        match build_regex(&self.query, &self.options) {
+            Ok(regex) => {
+                let mut matches = find_all(rope, &regex);
+                matches.retain(|m| self.in_scope(m));
+                self.matches = matches;
+            }
+            Err(e) => {
+                debug!("invalid search query {:?}: {}", &self.query, e);
+                self.error = Some("invalid regex".to_string());
+            }
+        }
+    }
+
+    pub fn matches(&self) -> &Vec<Range<usize>> {
+        &self.matches
+    }
+
+    /// First match starting at offset or after it, wrapping around the end of buffer.
+    pub fn next_match(&self, offset : usize) -> Option<Range<usize>> {
+        self.matches.iter().find(|m| m.start >= offset).or(self.matches.first()).cloned()
+    }
+
+    /// Last match starting before offset, wrapping around the beginning of buffer.
+    pub fn previous_match(&self, offset : usize) -> Option<Range<usize>> {
+        self.matches.iter().rev().find(|m| m.start < offset).or(self.matches.last()).cloned()
+    }
+
+    /// Returns whether offset is covered by any match.
+    pub fn is_match_at(&self, offset : usize) -> bool {
+        // number of matches starting at offset or before it.
+        let idx = match self.matches.binary_search_by_key(&offset, |m| m.start) {
+            Ok(idx) => idx + 1,
+            Err(idx) => idx,
+        };
+        idx > 0 && offset < self.matches[idx - 1].end
+    }
+
+    /// Text describing the state of search, like "3/17", "no matches" or an error.
+    pub fn status(&self, current : Option<&Range<usize>>) -> String {
+        if let Some(ref error) = self.error {
+            return error.clone();
+        }
+        if self.query.is_empty() {
+            return "".to_string();
+        }
+        if self.matches.is_empty() {
+            return "no matches".to_string();
+        }
+
+        let current_idx_op =
+            current.and_then(|range| self.matches.iter().position(|m| m == range));
+        match current_idx_op {
+            Some(idx) => format!("{}/{}", idx + 1, self.matches.len()),
+            None => format!("{} matches", self.matches.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(text : &str, query : &str, options : &SearchOptions) -> Vec<Range<usize>> {
+        let rope = Rope::from_str(text);
+        find_all(&rope, &build_regex(query, options).unwrap())
+    }
+
+    #[test]
+    fn search_options() {
+        let text = "Foo foo.bar foobar ąfoo";
+        let mut options = SearchOptions::default();
+        assert_eq!(search(text, "foo", &options), vec![0..3, 4..7, 12..15, 20..23]);
+
+        options.case_sensitive = true;
+        assert_eq!(search(text, "foo", &options), vec![4..7, 12..15, 20..23]);
+
+        options.whole_word = true;
+        assert_eq!(search(text, "foo", &options), vec![4..7]);
+
+        options.whole_word = false;
+        assert_eq!(search(text, "o.b", &options), vec![6..9]);
+        options.regex = true;
+        assert_eq!(search(text, "o.b", &options), vec![6..9, 13..16]);
+    }
+
+    #[test]
+    fn next_and_previous_wrap_around() {
+        let mut find_bar = FindBar::new("ab".to_string(), 0);
+        find_bar.refresh(&Rope::from_str("ab ab ab"), 0);
+
+        assert_eq!(find_bar.next_match(1), Some(3..5));
+        assert_eq!(find_bar.next_match(7), Some(0..2));
+        assert_eq!(find_bar.previous_match(3), Some(0..2));
+        assert_eq!(find_bar.previous_match(0), Some(6..8));
+        assert!(find_bar.is_match_at(4));
+        assert!(!find_bar.is_match_at(5));
+        assert_eq!(find_bar.status(Some(&(3..5))), "2/3");
+    }
+
+    #[test]
+    fn replace_with_capture_groups_in_scope() {
+        let rope = Rope::from_str("a=1 b=2 c=3");
+        let mut find_bar = FindBar::new(r"(\w)=(\d)".to_string(), 0);
+        find_bar.toggle_regex();
+        find_bar.enable_replace();
+        for c in "$2:${1}".chars() {
+            find_bar.push_char(c);
+        }
+
+        let all = find_bar.all_replacements(&rope);
+        assert_eq!(
+            all,
+            vec![(0..3, "1:a".to_string()), (4..7, "2:b".to_string()), (8..11, "3:c".to_string())]
+        );
+
+        find_bar.set_scope(Some(vec![Cursor::with_selection(2, 11)]));
+        let in_scope = find_bar.all_replacements(&rope);
+        assert_eq!(in_scope, vec![(4..7, "2:b".to_string()), (8..11, "3:c".to_string())]);
+        assert_eq!(find_bar.replacement_for(&rope, &(8..11)), Some("3:c".to_string()));
+    }
+}
@@ -18,19 +18,20 @@ limitations under the License.
 // TODO(njskalski) maybe change names of some traits/structs
 // TODO(njskalski) add validation as a comparison between default settings and user overrides.
 // TODO(njskalski) add validation if commands are known (plugins must be loaded first)
-// TODO(njskalski) parse more keys.
 
 use cursive;
-use cursive::event::{Event, Key};
+use cursive::event::{Event, Key, MouseButton};
 use cursive::theme;
 use default_settings::*;
 use log;
 use serde_json as sj;
+use special_chars::WhitespaceMode;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind, Read};
 use std::iter::FromIterator;
 use std::rc::Rc;
+use std::time::Duration;
 
 pub type KeybindingsType = HashMap<Event, String>;
 
@@ -41,6 +42,17 @@ fn get_known_keys() -> HashSet<String> {
         known_keys.insert(s.to_string());
     }
 
+    for s in vec![
+        "left", "right", "up", "down", "home", "end", "pageup", "pagedown", "enter", "tab",
+        "insert",
+    ] {
+        known_keys.insert(s.to_string());
+    }
+
+    for i in 1..13 {
+        known_keys.insert(format!("f{}", i));
+    }
+
     let alphabet = (b'A'..b'z' + 1) // Start as u8
         .map(|c| c as char) // Convert all to chars
         .filter(|c| c.is_alphabetic())
@@ -53,6 +65,39 @@ fn get_known_keys() -> HashSet<String> {
     known_keys
 }
 
+// Keys that are not letters. These can be combined with any modifiers.
+fn named_key(name : &str) -> Option<Key> {
+    let key = match name {
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "enter" => Key::Enter,
+        "tab" => Key::Tab,
+        "insert" => Key::Ins,
+        "delete" => Key::Del,
+        "backspace" => Key::Backspace,
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        _ => return None,
+    };
+    Some(key)
+}
+
 fn color_hex_to_rgb(hex : &str) -> Result<theme::Color, Error> {
     if hex.len() != 7 {
         Err(Error::new(ErrorKind::Other, format!("Error parsing color \"{:?}\".", hex)))
@@ -70,9 +115,23 @@ fn color_hex_to_rgb(hex : &str) -> Result<theme::Color, Error> {
 }
 
 pub struct Settings {
-    tree :              sj::Value,
-    color_cache :       RefCell<HashMap<&'static str, cursive::theme::Color>>,
-    auto_highlighting : bool,
+    tree :                  sj::Value,
+    color_cache :           RefCell<HashMap<&'static str, cursive::theme::Color>>,
+    auto_highlighting :     bool,
+    history_memory_budget : usize,
+    clipboard_backend :     String,
+    indent_string :         String,
+    scroll_off :            usize,
+    horizontal_scroll_off : usize,
+    soft_wrap :             bool,
+    wrap_column :           usize,
+    tab_width :             usize,
+    show_whitespace :       WhitespaceMode,
+    rulers :                Vec<usize>,
+    status_bar_left :       Vec<String>,
+    status_bar_right :      Vec<String>,
+    notification_timeout :  Duration,
+    add_cursor_button :     MouseButton,
 }
 
 impl Settings {
@@ -105,6 +164,127 @@ impl Settings {
         self.auto_highlighting
     }
 
+    /// Returns memory budget of history of a single buffer, in bytes.
+    pub fn history_memory_budget(&self) -> usize {
+        self.history_memory_budget
+    }
+
+    /// Returns how long a message stays in the status row.
+    pub fn notification_timeout(&self) -> Duration {
+        self.notification_timeout
+    }
+
+    /// Returns mouse button that adds a cursor where it's clicked.
+    pub fn add_cursor_button(&self) -> MouseButton {
+        self.add_cursor_button
+    }
+
+    /// Returns name of clipboard backend: "auto", "system", "osc52" or "internal".
+    pub fn clipboard_backend(&self) -> &String {
+        &self.clipboard_backend
+    }
+
+    /// Returns string inserted by a single level of indentation.
+    pub fn indent_string(&self) -> &String {
+        &self.indent_string
+    }
+
+    /// Returns number of lines kept visible above and below the cursor when scrolling.
+    pub fn scroll_off(&self) -> usize {
+        self.scroll_off
+    }
+
+    /// Returns number of columns kept visible left and right of the cursor when scrolling.
+    pub fn horizontal_scroll_off(&self) -> usize {
+        self.horizontal_scroll_off
+    }
+
+    // Returns settings node of language files with given extension are written in, if any. Every
+    // entry of "languages" lists it's "extensions".
+    fn language_node(&self, extension : Option<&str>) -> Option<&sj::Value> {
+        let extension = extension?;
+        let languages = self.tree.get("languages")?.as_object()?;
+        languages.values().find(|language| {
+            language
+                .get("extensions")
+                .and_then(|node| node.as_array())
+                .map(|extensions| extensions.iter().any(|ext| ext.as_str() == Some(extension)))
+                .unwrap_or(false)
+        })
+    }
+
+    // Returns per-language override of "editor" option.
+    fn language_option(&self, extension : Option<&str>, key : &str) -> Option<&sj::Value> {
+        self.language_node(extension).and_then(|node| node.get(key))
+    }
+
+    /// Returns whether long lines of files with given extension are wrapped at view width.
+    pub fn soft_wrap(&self, extension : Option<&str>) -> bool {
+        self.language_option(extension, "soft_wrap")
+            .and_then(|node| node.as_bool())
+            .unwrap_or(self.soft_wrap)
+    }
+
+    /// Returns column lines are soft wrapped at (if view is wide enough), 0 meaning view width.
+    pub fn wrap_column(&self, extension : Option<&str>) -> usize {
+        self.language_option(extension, "wrap_column")
+            .and_then(|node| node.as_u64())
+            .map(|column| column as usize)
+            .unwrap_or(self.wrap_column)
+    }
+
+    /// Returns number of columns between tab stops in files with given extension.
+    pub fn tab_width(&self, extension : Option<&str>) -> usize {
+        self.language_option(extension, "tab_width")
+            .and_then(|node| node.as_u64())
+            .map(|width| width as usize)
+            .unwrap_or(self.tab_width)
+    }
+
+    /// Returns when whitespace of files with given extension is drawn with glyphs.
+    pub fn show_whitespace(&self, extension : Option<&str>) -> WhitespaceMode {
+        self.language_option(extension, "show_whitespace")
+            .and_then(|node| node.as_str())
+            .and_then(WhitespaceMode::from_name)
+            .unwrap_or(self.show_whitespace)
+    }
+
+    /// Returns columns rulers are drawn at in files with given extension.
+    pub fn rulers(&self, extension : Option<&str>) -> Vec<usize> {
+        self.language_option(extension, "rulers")
+            .and_then(Self::parse_rulers)
+            .unwrap_or_else(|| self.rulers.clone())
+    }
+
+    fn parse_rulers(node : &sj::Value) -> Option<Vec<usize>> {
+        node.as_array()?.iter().map(|column| column.as_u64().map(|c| c as usize)).collect()
+    }
+
+    /// Returns names of status bar segments: aligned to the left and to the right.
+    pub fn status_bar_segments(&self) -> (&Vec<String>, &Vec<String>) {
+        (&self.status_bar_left, &self.status_bar_right)
+    }
+
+    fn parse_segments(node : &sj::Value) -> Option<Vec<String>> {
+        node.as_array()?.iter().map(|name| name.as_str().map(|n| n.to_string())).collect()
+    }
+
+    /// Returns glyph special char is drawn with, like "whitespace/tab".
+    pub fn get_glyph(&self, selector : &'static str) -> String {
+        let mut ptr : Option<&sj::Value> = Some(&self.tree);
+        for lane in selector.split('/') {
+            ptr = ptr.map(|subtree| &subtree[lane]);
+        }
+
+        match ptr {
+            Some(&sj::Value::String(ref glyph)) => glyph.clone(),
+            anything_else => panic!(
+                "expected glyph, got {:?} in path {:?} (or earlier)",
+                anything_else, selector
+            ),
+        }
+    }
+
     // TODO(njskalski) I decided not to use Cursive's palette mechanism, because most views will be
     // using more than the default number of colors. So this method is obsolete.
     pub fn get_palette(&self) -> theme::Palette {
@@ -191,13 +371,26 @@ impl Settings {
             let last_str : &String = keys.last().unwrap();
             let letter : char = last_str.chars().last().unwrap();
 
-            let event = match (shift_in, alt_in, ctrl_in, last_str.as_str()) {
-                (_, _, _, "esc") => Event::Key(Key::Esc),
-                (false, false, true, "c") => Event::Exit, //this is special case
-                (false, false, false, _) => Event::Char(letter),
-                (false, true, false, _) => Event::AltChar(letter),
-                (false, false, true, _) => Event::CtrlChar(letter),
-                _ => panic!("unsupported key combination = {:?} (now).", option_keys),
+            let event = if let Some(key) = named_key(last_str.as_str()) {
+                match (shift_in, alt_in, ctrl_in) {
+                    (false, false, false) => Event::Key(key),
+                    (true, false, false) => Event::Shift(key),
+                    (false, true, false) => Event::Alt(key),
+                    (true, true, false) => Event::AltShift(key),
+                    (false, false, true) => Event::Ctrl(key),
+                    (true, false, true) => Event::CtrlShift(key),
+                    (false, true, true) => Event::CtrlAlt(key),
+                    _ => panic!("unsupported key combination = {:?} (now).", option_keys),
+                }
+            } else {
+                match (shift_in, alt_in, ctrl_in, last_str.as_str()) {
+                    (_, _, _, "esc") => Event::Key(Key::Esc),
+                    (false, false, true, "c") => Event::Exit, //this is special case
+                    (false, false, false, _) => Event::Char(letter),
+                    (false, true, false, _) => Event::AltChar(letter),
+                    (false, false, true, _) => Event::CtrlChar(letter),
+                    _ => panic!("unsupported key combination = {:?} (now).", option_keys),
+                }
             };
 
             // debug!("assigning {:?} to action {:?}", event, option_name);
@@ -239,10 +432,179 @@ impl Settings {
         };
         let auto_highlighting = auto_highlighting.unwrap();
 
+        let history_memory_budget_kb = tree
+            .get("performance")
+            .and_then(|node| node.get("history_memory_budget_kb"))
+            .and_then(|node| node.as_u64());
+
+        if history_memory_budget_kb.is_none() {
+            return None;
+        };
+        let history_memory_budget = history_memory_budget_kb.unwrap() as usize * 1024;
+
+        let clipboard_backend = tree
+            .get("clipboard")
+            .and_then(|node| node.get("backend"))
+            .and_then(|node| node.as_str())
+            .map(|s| s.to_string());
+
+        if clipboard_backend.is_none() {
+            return None;
+        };
+        let clipboard_backend = clipboard_backend.unwrap();
+
+        let indent_string = tree
+            .get("editor")
+            .and_then(|node| node.get("indent_string"))
+            .and_then(|node| node.as_str())
+            .map(|s| s.to_string());
+
+        if indent_string.is_none() {
+            return None;
+        };
+        let indent_string = indent_string.unwrap();
+
+        let scroll_off = tree
+            .get("editor")
+            .and_then(|node| node.get("scroll_off"))
+            .and_then(|node| node.as_u64());
+
+        if scroll_off.is_none() {
+            return None;
+        };
+        let scroll_off = scroll_off.unwrap() as usize;
+
+        let horizontal_scroll_off = tree
+            .get("editor")
+            .and_then(|node| node.get("horizontal_scroll_off"))
+            .and_then(|node| node.as_u64());
+
+        if horizontal_scroll_off.is_none() {
+            return None;
+        };
+        let horizontal_scroll_off = horizontal_scroll_off.unwrap() as usize;
+
+        let soft_wrap = tree
+            .get("editor")
+            .and_then(|node| node.get("soft_wrap"))
+            .and_then(|node| node.as_bool());
+
+        if soft_wrap.is_none() {
+            return None;
+        };
+        let soft_wrap = soft_wrap.unwrap();
+
+        let wrap_column = tree
+            .get("editor")
+            .and_then(|node| node.get("wrap_column"))
+            .and_then(|node| node.as_u64());
+
+        if wrap_column.is_none() {
+            return None;
+        };
+        let wrap_column = wrap_column.unwrap() as usize;
+
+        let tab_width = tree
+            .get("editor")
+            .and_then(|node| node.get("tab_width"))
+            .and_then(|node| node.as_u64());
+
+        if tab_width.is_none() {
+            return None;
+        };
+        let tab_width = tab_width.unwrap() as usize;
+
+        let show_whitespace = tree
+            .get("editor")
+            .and_then(|node| node.get("show_whitespace"))
+            .and_then(|node| node.as_str())
+            .and_then(WhitespaceMode::from_name);
+
+        if show_whitespace.is_none() {
+            return None;
+        };
+        let show_whitespace = show_whitespace.unwrap();
+
+        let rulers = tree
+            .get("editor")
+            .and_then(|node| node.get("rulers"))
+            .and_then(Self::parse_rulers);
+
+        if rulers.is_none() {
+            return None;
+        };
+        let rulers = rulers.unwrap();
+
+        let status_bar_left = tree
+            .get("status_bar")
+            .and_then(|node| node.get("left"))
+            .and_then(Self::parse_segments);
+
+        if status_bar_left.is_none() {
+            return None;
+        };
+        let status_bar_left = status_bar_left.unwrap();
+
+        let status_bar_right = tree
+            .get("status_bar")
+            .and_then(|node| node.get("right"))
+            .and_then(Self::parse_segments);
+
+        if status_bar_right.is_none() {
+            return None;
+        };
+        let status_bar_right = status_bar_right.unwrap();
+
+        let notification_timeout_ms = tree
+            .get("notifications")
+            .and_then(|node| node.get("timeout_ms"))
+            .and_then(|node| node.as_u64());
+
+        if notification_timeout_ms.is_none() {
+            return None;
+        };
+        let notification_timeout = Duration::from_millis(notification_timeout_ms.unwrap());
+
+        let add_cursor_button = tree
+            .get("mouse")
+            .and_then(|node| node.get("add_cursor_button"))
+            .and_then(|node| node.as_str())
+            .map(|name| match name {
+                "middle" => MouseButton::Middle,
+                "right" => MouseButton::Right,
+                // left click has to keep moving the cursor.
+                _ => {
+                    warn!(
+                        "unsupported mouse/add_cursor_button {:?}, using \"middle\"",
+                        name
+                    );
+                    MouseButton::Middle
+                }
+            });
+
+        if add_cursor_button.is_none() {
+            return None;
+        };
+        let add_cursor_button = add_cursor_button.unwrap();
+
         Some(Settings {
-            tree :              tree,
-            color_cache :       RefCell::new(HashMap::new()),
-            auto_highlighting : auto_highlighting,
+            tree :                  tree,
+            color_cache :           RefCell::new(HashMap::new()),
+            auto_highlighting :     auto_highlighting,
+            history_memory_budget : history_memory_budget,
+            clipboard_backend :     clipboard_backend,
+            indent_string :         indent_string,
+            scroll_off :            scroll_off,
+            horizontal_scroll_off : horizontal_scroll_off,
+            soft_wrap :             soft_wrap,
+            wrap_column :           wrap_column,
+            tab_width :             tab_width,
+            show_whitespace :       show_whitespace,
+            rulers :                rulers,
+            status_bar_left :       status_bar_left,
+            status_bar_right :      status_bar_right,
+            notification_timeout :  notification_timeout,
+            add_cursor_button :     add_cursor_button,
         })
     }
 }
@@ -256,4 +618,19 @@ mod tests {
     fn default_settings_parses() {
         let settins = Settings::load_default();
     }
+
+    #[test]
+    fn language_options_override_editor_ones() {
+        let settings = Settings::load_default();
+        assert!(settings.soft_wrap(Some("md")));
+        assert!(!settings.soft_wrap(Some("rs")));
+        assert!(!settings.soft_wrap(None));
+        assert_eq!(settings.wrap_column(Some("md")), 0);
+        assert_eq!(settings.tab_width(Some("go")), 8);
+        assert_eq!(settings.tab_width(Some("rs")), 4);
+        assert_eq!(settings.show_whitespace(Some("mk")), WhitespaceMode::Always);
+        assert_eq!(settings.show_whitespace(None), WhitespaceMode::Selection);
+        assert_eq!(settings.rulers(Some("rs")), vec![100]);
+        assert_eq!(settings.rulers(Some("txt")), vec![80]);
+    }
 }
@@ -15,28 +15,21 @@ limitations under the License.
 */
 
 // missing to MVP:
-// - selection and copy to clipboard (paste already works)
 // - underlining the symbols that offer navigation options (Language Protocol)
-// - status bar (row, column, readonly/rw mode, whether modified, whether out-of-sync)
-// - search and replace
-// - some way to set additional cursors
 // missing nice-to-haves
-// - regex in "search"
 // - normal/insert mode
 // other ideas:
 // - python script in replace
 
-// TODO(njskalski) never allow overlapping cursors
 // TODO(njskalski) update cursors on autoreload from hard drive (autoreload enabled if non-modified,
-// and not disabled in options) TODO(njskalski) use View::layout instead of View::required_size to
-// determine window size.
+// and not disabled in options).
 
 use time;
 
 use buffer_state_observer::BufferStateObserver;
-use clipboard;
-use clipboard::ClipboardProvider;
-use content_provider::{EditEvent, RopeBasedContentProvider};
+use clipboard_backend::ClipboardBackend;
+use cursor::{map_cursors, normalize_cursors, Cursor};
+use content_provider::{EditBatch, EditEvent, RevisionId, RopeBasedContentProvider};
 use cursive::direction::Direction;
 use cursive::event::{Event, EventResult, Key, MouseButton, MouseEvent};
 use cursive::theme::{Color, ColorType};
@@ -50,43 +43,69 @@ use cursive::views::ViewRef;
 use cursive::{Printer, With, XY};
 use events::IChannel;
 use events::IEvent;
+use kill_ring::{join_pieces, KillRing};
+use line_layout;
+use line_layout::{row_of_char, DisplayRow};
+use line_operations;
+use line_operations::LineOperationResult;
+use lsp_client::LspStatus;
+use notifications::{Notifications, Severity};
 use rich_content::{RichContent, RichLine};
+use search::{replacement_events, FindBar, FindBarField};
 use ropey::Rope;
 use settings::Settings;
 use sly_view::SlyView;
+use special_chars::{SpecialChar, WhitespaceMode};
+use status_bar;
+use status_bar::StatusInfo;
 use std::borrow::BorrowMut;
+use std::cell::Cell;
 use std::cell::Ref;
 use std::cell::RefCell;
 use std::cmp;
 use std::cmp::min;
-use std::collections::HashMap;
 use std::iter;
+use std::ops::Range;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use std::usize::MAX;
+use text_navigation;
+use text_navigation::{line_end_without_newline, word_range_at};
 use unicode_segmentation;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 use view_handle::ViewHandle;
 
 const INDEX_MARGIN : usize = 1;
-const PAGE_WIDTH : usize = 80;
-
-//Cursor: offset in CHARS and "preferred column" (probably cached y coordinate, don't remember).
-type Cursor = (usize, Option<usize>);
+const MULTI_CLICK_INTERVAL_MS : u64 = 400;
+const WHEEL_SCROLL_LINES : usize = 3;
+const WRAP_MARKER : char = '\u{21AA}';
 
 //const NEWLINE_DRAWING : char = '\u{2424}';
 
 pub struct SlyTextView {
-    channel :               IChannel, // interface feedback channel
-    buffer :                BufferStateObserver,
-    cursors :               Vec<Cursor>, // offset in CHARS, preferred column
-    position :              Vec2,        // position of upper left corner of view in file
-    last_view_size :        Option<Vec2>, //not sure if using properly
-    settings :              Rc<RefCell<Settings>>,
-    clipboard_context :     clipboard::ClipboardContext,
-    special_char_mappings : HashMap<char, char>,
-    handle :                ViewHandle,
-    syntax_highlighting :   bool, //local override of global setting.
+    channel :             IChannel, // interface feedback channel
+    buffer :              BufferStateObserver,
+    cursors :             Vec<Cursor>, // sorted, never overlapping, offsets in CHARS
+    position :            Vec2,        // first visible line (y) and column (x)
+    last_view_size :      Option<Vec2>, // set in layout, None until first one
+    settings :            Rc<RefCell<Settings>>,
+    clipboard :           Rc<RefCell<Box<ClipboardBackend>>>,
+    kill_ring :           Rc<RefCell<KillRing>>,
+    lsp_status :          Rc<Cell<LspStatus>>,
+    notifications :       Rc<RefCell<Notifications>>, // the current one is shown in status bar
+    handle :              ViewHandle,
+    syntax_highlighting : bool, //local override of global setting.
+    find_bar :            Option<FindBar>, // drawn in the last row of view when open
+    last_click :          Option<(Instant, usize, usize)>, // time, offset, clicks in a row
+    soft_wrap :           bool,
+    wrap_column :         usize, // 0 means view width
+    tab_width :           usize,
+    whitespace_mode :     WhitespaceMode,
+    rulers :              Vec<usize>, // columns, relative to row
+    // Set when edit was submitted. Buffer applies it later, so scrolling to first cursor waits
+    // for the next layout.
+    follow_cursor : bool,
 }
 
 impl SlyView for SlyTextView {
@@ -98,22 +117,50 @@ impl SlyView for SlyTextView {
 impl SlyTextView {
     pub fn new(
         settings : Rc<RefCell<Settings>>,
+        kill_ring : Rc<RefCell<KillRing>>,
+        clipboard : Rc<RefCell<Box<ClipboardBackend>>>,
+        lsp_status : Rc<Cell<LspStatus>>,
+        notifications : Rc<RefCell<Notifications>>,
         buffer : BufferStateObserver,
         channel : IChannel,
     ) -> IdView<Self> {
         let syntax_highlighting : bool = settings.borrow().auto_highlighting_enabled();
+        let extension : Option<String> = buffer
+            .get_path()
+            .and_then(|path| path.extension().map(|ext| ext.to_string_lossy().to_string()));
+        let (soft_wrap, wrap_column, tab_width, whitespace_mode, rulers) = {
+            let settings = settings.borrow();
+            let extension = extension.as_ref().map(|ext| ext.as_str());
+            (
+                settings.soft_wrap(extension),
+                settings.wrap_column(extension),
+                settings.tab_width(extension),
+                settings.show_whitespace(extension),
+                settings.rulers(extension),
+            )
+        };
 
         let mut view = SlyTextView {
-            channel :               channel,
-            buffer :                buffer,
-            cursors :               vec![(0, None)],
-            position :              Vec2::new(0, 0),
-            last_view_size :        None,
-            settings :              settings,
-            clipboard_context :     clipboard::ClipboardProvider::new().unwrap(),
-            special_char_mappings : hashmap!['\n' => '\u{21B5}'],
-            handle :                ViewHandle::new(),
-            syntax_highlighting :   syntax_highlighting,
+            channel :             channel,
+            buffer :              buffer,
+            cursors :             vec![Cursor::new(0)],
+            position :            Vec2::new(0, 0),
+            last_view_size :      None,
+            settings :            settings,
+            clipboard :           clipboard,
+            kill_ring :           kill_ring,
+            lsp_status :          lsp_status,
+            notifications :       notifications,
+            handle :              ViewHandle::new(),
+            syntax_highlighting : syntax_highlighting,
+            find_bar :            None,
+            last_click :          None,
+            soft_wrap :           soft_wrap,
+            wrap_column :         wrap_column,
+            tab_width :           tab_width,
+            whitespace_mode :     whitespace_mode,
+            rulers :              rulers,
+            follow_cursor :       false,
         };
 
         if syntax_highlighting && !view.syntax_highlighting_on() {
@@ -131,8 +178,126 @@ impl SlyTextView {
         &self.buffer
     }
 
-    fn submit_events(&mut self, events : Vec<EditEvent>) {
-        self.channel.send(IEvent::BufferEditEvent(self.buffer.buffer_id(), events)).unwrap()
+    // Should be called *after* cursors were updated, as their current state is stored in history.
+    fn submit_events(
+        &mut self,
+        events : Vec<EditEvent>,
+        cursors_before : Vec<Cursor>,
+        coalesce : bool,
+    ) {
+        if self.buffer.is_read_only() {
+            self.cursors = cursors_before;
+            let text = "buffer is read-only".to_string();
+            self.channel.send(IEvent::Notify(Severity::Warning, text)).unwrap();
+            return;
+        }
+
+        let batch = EditBatch {
+            events :         events,
+            cursors_before : cursors_before,
+            cursors_after :  self.cursors.clone(),
+            coalesce :       coalesce,
+        };
+        self.follow_cursor = true;
+        let event = IEvent::BufferEditEvent(self.buffer.buffer_id(), self.handle.clone(), batch);
+        self.channel.send(event).unwrap()
+    }
+
+    /// Reverts last change in buffer, restoring cursors from before it. Returns events that
+    /// reverted it, so cursors of other views of the buffer can be mapped through them.
+    pub fn undo(&mut self) -> Vec<EditEvent> {
+        let result_op = self.buffer.undo();
+        self.restore_cursors(result_op)
+    }
+
+    /// Re-applies last reverted change in buffer, restoring cursors from after it. Returns events
+    /// of the change.
+    pub fn redo(&mut self) -> Vec<EditEvent> {
+        let result_op = self.buffer.redo();
+        self.restore_cursors(result_op)
+    }
+
+    /// Sets buffer to any revision from its history, restoring cursors from after it was made.
+    /// Returns events that led to that revision.
+    pub fn jump_to_revision(&mut self, id : RevisionId) -> Vec<EditEvent> {
+        let result_op = self.buffer.jump_to_revision(id);
+        self.restore_cursors(result_op)
+    }
+
+    /// Moves the only cursor to given line and column (in chars, both 0 based), clamping both to
+    /// buffer content.
+    pub fn go_to(&mut self, line : usize, column : usize) {
+        let offset = {
+            let content = self.buffer.borrow_content();
+            let rope = content.get_lines();
+            let line = cmp::min(line, rope.len_lines() - 1);
+            let line_begin = rope.line_to_char(line);
+            cmp::min(line_begin + column, line_end_without_newline(rope, line))
+        };
+
+        self.cursors = vec![Cursor::new(offset)];
+        if self.last_view_size.is_some() {
+            self.make_sure_first_cursor_visible();
+        }
+    }
+
+    /// Moves the only cursor to the end of buffer.
+    pub fn go_to_end(&mut self) {
+        let len_chars = self.buffer.borrow_content().get_lines().len_chars();
+        self.cursors = vec![Cursor::new(len_chars)];
+        if self.last_view_size.is_some() {
+            self.make_sure_first_cursor_visible();
+        }
+    }
+
+    /// Called after buffer was reloaded from disk (like the log growing). If the only cursor was at
+    /// the old end of buffer, it goes to the new one, so the view follows the file. Other cursors
+    /// get clamped to content.
+    pub fn follow_end(&mut self, old_len_chars : usize) {
+        let at_end = self.cursors.len() == 1
+            && self.cursors[0].selection().is_none()
+            && self.cursors[0].offset == old_len_chars;
+        if at_end {
+            self.go_to_end();
+            return;
+        }
+
+        let len_chars = self.buffer.borrow_content().get_lines().len_chars();
+        for c in &mut self.cursors {
+            c.clamp(len_chars);
+        }
+    }
+
+    // Returns events applied to buffer, if any.
+    fn restore_cursors(
+        &mut self,
+        result_op : Option<(Vec<EditEvent>, Vec<Cursor>)>,
+    ) -> Vec<EditEvent> {
+        let (events, mut cursors) = match result_op {
+            Some(result) => result,
+            None => {
+                debug!("nothing to undo/redo");
+                return Vec::new();
+            }
+        };
+
+        // Batches that did not come from a view can carry no cursors, current ones are mapped
+        // then.
+        if cursors.is_empty() {
+            cursors = self.cursors.clone();
+            map_cursors(&mut cursors, &events);
+        }
+
+        let len_chars = self.buffer.borrow_content().get_lines().len_chars();
+        for c in &mut cursors {
+            c.clamp(len_chars);
+        }
+
+        self.cursors = cursors;
+        if self.last_view_size.is_some() {
+            self.make_sure_first_cursor_visible();
+        }
+        events
     }
 
     /// Returns the position of the cursor in the content string.
@@ -140,10 +305,40 @@ impl SlyTextView {
         &self.cursors
     }
 
+    /// Maps cursors through events applied to buffer elsewhere (like in other view of the same
+    /// buffer), so they keep pointing at the same text.
+    pub fn map_cursors_through(&mut self, events : &Vec<EditEvent>) {
+        map_cursors(&mut self.cursors, events);
+    }
+
+    /// Takes cursors and scroll position of other view of the same buffer, so a new pane starts
+    /// where the one it was split from is.
+    pub fn copy_position_from(&mut self, other : &SlyTextView) {
+        self.cursors = other.cursors.clone();
+        self.position = other.position;
+    }
+
     pub fn syntax_highlighting_on(&self) -> bool {
         self.syntax_highlighting && self.buffer.borrow_content().is_rich_content_enabled()
     }
 
+    pub fn soft_wrap_on(&self) -> bool {
+        self.soft_wrap
+    }
+
+    /// Turns wrapping of long lines at view width (or configured column) on or off.
+    pub fn set_soft_wrap(&mut self, enabled : bool) {
+        self.soft_wrap = enabled;
+        self.position.x = 0;
+        // preferred columns are relative to rows when wrapping, to lines otherwise.
+        for c in &mut self.cursors {
+            c.preferred_column = None;
+        }
+        if self.last_view_size.is_some() {
+            self.make_sure_first_cursor_visible();
+        }
+    }
+
     /// Returns value syntax highlighting is set to. May be different than requested.
     pub fn set_syntax_highlighting(&mut self, enabled : bool) -> bool {
         if enabled {
@@ -168,17 +363,39 @@ impl View for SlyTextView {
         let cursors = &self.cursors; //: Vec<Vec2> = textWindow.filter_cursors(&self.cursors);
         let lines = content.get_lines();
 
-        let view_size = self.last_view_size.expect("view size not known.");
+        let view_size = printer.size;
+        let text_height = self.text_height();
+        let text_width = view_size.x.saturating_sub(index_length + INDEX_MARGIN);
+        let selection_background = ColorType::Color(
+            self.settings_ref().get_color("theme/text_view/selection_background_color"),
+        );
+        let match_background = ColorType::Color(
+            self.settings_ref().get_color("theme/text_view/search_match_background_color"),
+        );
+        let whitespace_color =
+            ColorType::Color(self.settings_ref().get_color("theme/text_view/whitespace_color"));
+        let control_char_color =
+            ColorType::Color(self.settings_ref().get_color("theme/text_view/control_char_color"));
+        let trailing_whitespace_background = ColorType::Color(
+            self.settings_ref().get_color("theme/text_view/trailing_whitespace_background_color"),
+        );
+        let ruler_background = ColorType::Color(
+            self.settings_ref().get_color("theme/text_view/ruler_background_color"),
+        );
+
+        let rows = self.display_rows(lines, text_height);
 
         //index + INDEX_MARGIN ----------------------------------------------------------------
-        for line_no in
-            (self.position.y)..(cmp::min(lines.len_lines(), self.position.y + view_size.y))
-        {
+        for (y, row) in rows.iter().enumerate() {
             let mut x : usize = 0;
 
-            let y = line_no - self.position.y;
-            let line_desc = (line_no + 1).to_string();
-            let local_index_length = line_desc.len(); //logarithm? never heard of it.
+            // continuation rows of wrapped lines get a marker instead of line number.
+            let line_desc = if row.is_continuation {
+                WRAP_MARKER.to_string()
+            } else {
+                (row.line + 1).to_string()
+            };
+            let local_index_length = line_desc.width(); //logarithm? never heard of it.
 
             printer.with_color(ColorStyle::secondary(), |printer| {
                 for _ in 0..(index_length - local_index_length) {
@@ -197,14 +414,26 @@ impl View for SlyTextView {
         }
         // end of index + INDEX_MARGIN --------------------------------------------------------
 
+        //rulers ------------------------------------------------------------------------------
+        // drawn under the text as well, past the end of lines and buffer.
+        let mut ruler_style = ColorStyle::primary();
+        ruler_style.back = ruler_background;
+        for ruler in &self.rulers {
+            if *ruler < self.position.x || *ruler - self.position.x >= text_width {
+                continue;
+            }
+            let x = *ruler - self.position.x + index_length + INDEX_MARGIN;
+            printer.with_color(ruler_style, |printer| {
+                printer.print_vline((x, 0), text_height, " ");
+            });
+        }
+        //end of rulers -----------------------------------------------------------------------
+
         //line --------------------------------------------------------------------------------
 
-        for line_no in
-            (self.position.y)..(cmp::min(lines.len_lines(), self.position.y + view_size.y))
-        {
-            let y = line_no - self.position.y;
+        for (y, row) in rows.iter().enumerate() {
+            let line_no = row.line;
             let line_offset = &content.get_lines().line_to_char(line_no);
-            let line = &content.get_lines().line(line_no);
             let rich_line_op = self.buffer.borrow_content().get_rich_line(line_no);
 
             if rich_line_op.is_some()
@@ -213,29 +442,55 @@ impl View for SlyTextView {
                 error!("rich line {:?}: {:?}", line_no, rich_line_op);
             }
 
-            //this allow a cursor *after* the last character. It's actually needed.
-            let add = if line_no == lines.len_lines() - 1 { 1 } else { 0 };
+            // row of the last line has a cell after it's last char, for cursor.
+            let line_slice = content.get_lines().line(line_no);
+            let line_len = line_slice.len_chars();
+            let text_end = line_end_without_newline(content.get_lines(), line_no) - line_offset;
+            let mut trailing_begin = text_end;
+            while trailing_begin > 0 && line_slice.char(trailing_begin - 1).is_whitespace() {
+                trailing_begin -= 1;
+            }
 
-            for char_idx in 0..(line.len_chars() + add) {
+            for grapheme in &row.graphemes {
+                if grapheme.column < self.position.x {
+                    continue;
+                }
+                let column = grapheme.column - self.position.x;
+                if column + grapheme.width > text_width {
+                    break;
+                }
+                let char_idx = grapheme.chars.start;
                 let char_offset = line_offset + char_idx;
 
-                let mut special_char = false;
-                let symbol : char = if line.len_chars() > char_idx {
-                    let c = line.char(char_idx);
-                    if !self.special_char_mappings.contains_key(&c) {
-                        c
-                    } else {
-                        special_char = true;
-                        self.special_char_mappings[&c]
+                let special_op =
+                    if char_idx < line_len { SpecialChar::of(&grapheme.text) } else { None };
+                let selected = self.had_selection_at(&char_offset);
+                let shown = special_op
+                    .map(|special| !special.is_whitespace() || self.whitespace_mode.shows(selected))
+                    .unwrap_or(false);
+                let trailing = char_idx >= trailing_begin && char_idx < text_end;
+
+                // hidden whitespace is drawn blank, tabs are padded up to the next tab stop.
+                let symbol : String = match special_op {
+                    None => grapheme.text.clone(),
+                    Some(special) => {
+                        let glyph = if shown {
+                            self.settings_ref().get_glyph(special.glyph_selector(trailing))
+                        } else {
+                            " ".to_string()
+                        };
+                        glyph + &" ".repeat(grapheme.width - 1)
                     }
-                } else {
-                    ' '
                 };
 
-                let color_style : ColorStyle = if self.had_cursor_at(&char_offset) {
+                // cursor may be inside of grapheme, after a combining mark was typed.
+                let has_cursor =
+                    grapheme.chars.clone().any(|idx| self.had_cursor_at(&(line_offset + idx)));
+
+                let color_style : ColorStyle = if has_cursor {
                     ColorStyle::highlight()
                 } else {
-                    if char_idx <= 80 && !special_char {
+                    let mut style = if special_op.is_none() {
                         let mut someColor = ColorStyle::primary();
 
                         match &rich_line_op {
@@ -250,7 +505,27 @@ impl View for SlyTextView {
                         someColor
                     } else {
                         ColorStyle::secondary()
+                    };
+
+                    if shown {
+                        style.front = match special_op {
+                            Some(ref special) if special.is_whitespace() => whitespace_color,
+                            _ => control_char_color,
+                        };
+                    }
+
+                    if selected {
+                        style.back = selection_background;
+                    } else if self.had_match_at(char_offset) {
+                        style.back = match_background;
+                    } else if trailing && shown {
+                        style.back = trailing_whitespace_background;
+                    } else if self.rulers.iter().any(|ruler| {
+                        *ruler >= grapheme.column && *ruler < grapheme.column + grapheme.width
+                    }) {
+                        style.back = ruler_background;
                     }
+                    style
                 };
 
                 // let effect = if self.cursors.contains(&char_offset) {
@@ -262,24 +537,56 @@ impl View for SlyTextView {
 
                 printer.with_color(color_style, |printer| {
                     printer.with_effect(effect, |printer| {
-                        printer.print(
-                            (char_idx + index_length + INDEX_MARGIN, y),
-                            &symbol.to_string(),
-                        );
+                        printer.print((column + index_length + INDEX_MARGIN, y), &symbol);
                     });
                 });
             }
         }
         //end of line ------------------------------------------------------------------------
+
+        if let Some(ref find_bar) = self.find_bar {
+            self.draw_find_bar(find_bar, &printer.offset((0, text_height)), view_size.x);
+        }
+
+        if self.status_bar_height() > 0 && view_size.y > 0 {
+            self.draw_status_bar(&printer.offset((0, view_size.y - 1)), view_size.x);
+        }
     }
 
     fn required_size(&mut self, constraint : Vec2) -> Vec2 {
-        self.last_view_size = Some(constraint);
         //        debug!("got constraint {:?}", constraint);
         constraint //now we just take whole available space
     }
 
+    fn layout(&mut self, size : Vec2) {
+        // buffer could have been changed since last frame, also by someone else than this view.
+        let (len_chars, len_lines) = {
+            let content = self.buffer.borrow_content();
+            (content.get_lines().len_chars(), content.get_lines().len_lines())
+        };
+        for c in &mut self.cursors {
+            c.clamp(len_chars);
+        }
+        self.position.y = cmp::min(self.position.y, len_lines - 1);
+        self.refresh_find_bar();
+
+        let resized = self.last_view_size != Some(size);
+        self.last_view_size = Some(size);
+        if resized || self.follow_cursor {
+            self.follow_cursor = false;
+            self.make_sure_first_cursor_visible();
+        }
+    }
+
     fn on_event(&mut self, event : Event) -> EventResult {
+        if self.find_bar.is_some() && self.find_bar_on_event(&event) {
+            return EventResult::Consumed(None);
+        }
+
+        if let Event::Mouse { offset, position, event : mouse_event } = event {
+            return self.on_mouse_event(offset, position, mouse_event);
+        }
+
         let text_keybindings = self.settings_ref().get_keybindings("text");
         if text_keybindings.contains_key(&event) {
             let action : &String = &text_keybindings[&event];
@@ -287,19 +594,139 @@ impl View for SlyTextView {
             let mut consumed = true;
             match action.as_str() {
                 "paste" => {
-                    let cc = self.clipboard_context.get_contents();
-                    match cc {
-                        Ok(ref string) => {
-                            self.add_text(string);
-                        }
-                        Err(err_box) => {
-                            info!("Error while attempting to access clipboard: {:?}", err_box);
-                        }
-                    };
-                    debug!("pasted");
+                    self.paste();
+                }
+                "paste_from_history" => {
+                    self.channel.send(IEvent::ShowKillRing).unwrap();
                 }
                 "copy" => {
-                    debug!("copy! (NOT IMPLEMENTED)");
+                    self.copy();
+                }
+                "cut" => {
+                    self.cut();
+                }
+                "select_all" => {
+                    self.select_all();
+                }
+                "find" => {
+                    self.open_find_bar();
+                }
+                "replace" => {
+                    self.open_find_bar();
+                    self.find_bar.as_mut().unwrap().enable_replace();
+                }
+                "add_cursor_above" => {
+                    self.add_cursor_above();
+                }
+                "add_cursor_below" => {
+                    self.add_cursor_below();
+                }
+                "add_next_occurrence" => {
+                    self.add_next_occurrence();
+                }
+                "select_all_occurrences" => {
+                    self.select_all_occurrences();
+                }
+                "split_selection_into_lines" => {
+                    self.split_selection_into_lines();
+                }
+                "word_left" | "select_word_left" => {
+                    let extend = action.starts_with("select_");
+                    self.move_all_cursors_with(text_navigation::word_start_before, extend);
+                }
+                "word_right" | "select_word_right" => {
+                    let extend = action.starts_with("select_");
+                    self.move_all_cursors_with(text_navigation::word_end_after, extend);
+                }
+                "line_start" | "select_line_start" => {
+                    let extend = action.starts_with("select_");
+                    self.move_all_cursors_with(text_navigation::smart_line_start, extend);
+                }
+                "line_end" | "select_line_end" => {
+                    let extend = action.starts_with("select_");
+                    self.move_all_cursors_with(text_navigation::smart_line_end, extend);
+                }
+                "document_start" | "select_document_start" => {
+                    let extend = action.starts_with("select_");
+                    self.move_all_cursors_with(|_, _| 0, extend);
+                }
+                "document_end" | "select_document_end" => {
+                    let extend = action.starts_with("select_");
+                    self.move_all_cursors_with(|rope, _| rope.len_chars(), extend);
+                }
+                "delete_forward" => {
+                    self.delete_with(text_navigation::grapheme_end_after);
+                }
+                "delete_word_backward" => {
+                    self.delete_with(text_navigation::word_start_before);
+                }
+                "delete_word_forward" => {
+                    self.delete_with(text_navigation::word_end_after);
+                }
+                "delete_to_line_end" => {
+                    self.delete_with(text_navigation::line_end_or_next_line);
+                }
+                "duplicate" => {
+                    self.apply_line_operation(|rope, cursors, _| {
+                        line_operations::duplicate(rope, cursors)
+                    });
+                }
+                "move_lines_up" => {
+                    self.apply_line_operation(|rope, cursors, _| {
+                        line_operations::move_lines_up(rope, cursors)
+                    });
+                }
+                "move_lines_down" => {
+                    self.apply_line_operation(|rope, cursors, _| {
+                        line_operations::move_lines_down(rope, cursors)
+                    });
+                }
+                "join_lines" => {
+                    self.apply_line_operation(|rope, cursors, _| {
+                        line_operations::join_lines(rope, cursors)
+                    });
+                }
+                "delete_lines" => {
+                    self.apply_line_operation(|rope, cursors, _| {
+                        line_operations::delete_lines(rope, cursors)
+                    });
+                }
+                "insert_line_below" => {
+                    self.apply_line_operation(|rope, cursors, _| {
+                        line_operations::insert_line(rope, cursors, false)
+                    });
+                }
+                "insert_line_above" => {
+                    self.apply_line_operation(|rope, cursors, _| {
+                        line_operations::insert_line(rope, cursors, true)
+                    });
+                }
+                "indent" => {
+                    // without selections, it's just typing the indentation.
+                    if self.cursors.iter().any(|c| c.has_selection()) {
+                        self.apply_line_operation(line_operations::indent);
+                    } else {
+                        let indent_string = self.settings_ref().indent_string().clone();
+                        self.add_text(&indent_string);
+                    }
+                }
+                "dedent" => {
+                    self.apply_line_operation(line_operations::dedent);
+                }
+                "scroll_up" => {
+                    self.scroll_up(1);
+                }
+                "scroll_down" => {
+                    self.scroll_down(1);
+                }
+                "center_on_cursor" => {
+                    self.center_on_cursor();
+                }
+                "undo" => {
+                    self.channel.send(IEvent::BufferUndo(self.buffer.buffer_id())).unwrap();
+                }
+                "redo" => {
+                    self.channel.send(IEvent::BufferRedo(self.buffer.buffer_id())).unwrap();
                 }
                 _ => consumed = false,
             };
@@ -322,6 +749,10 @@ impl View for SlyTextView {
                         debug!("syntax highlighting unavailable"); // TODO(njskalski): add some msg.
                     }
                 }
+                "toggle_soft_wrap" => {
+                    let enabled = !self.soft_wrap_on();
+                    self.set_soft_wrap(enabled);
+                }
                 _ => consumed = false,
             };
             if consumed {
@@ -342,24 +773,44 @@ impl View for SlyTextView {
                 debug!("hit backspace");
             }
             Event::Key(Key::Left) => {
-                &self.move_all_cursors_left();
+                &self.move_all_cursors_left(false);
             }
             Event::Key(Key::Right) => {
-                &self.move_all_cursors_right();
+                &self.move_all_cursors_right(false);
             }
             Event::Key(Key::Up) => {
-                &self.move_all_cursors_up(1);
+                &self.move_all_cursors_up(1, false);
             }
             Event::Key(Key::Down) => {
-                &self.move_all_cursors_down(1);
+                &self.move_all_cursors_down(1, false);
             }
             Event::Key(Key::PageUp) => {
-                let height = self.last_view_size.unwrap().y;
-                &self.move_all_cursors_up(height);
+                let height = cmp::max(self.text_height(), 1);
+                &self.move_all_cursors_up(height, false);
             }
             Event::Key(Key::PageDown) => {
-                let height = self.last_view_size.unwrap().y;
-                &self.move_all_cursors_down(height);
+                let height = cmp::max(self.text_height(), 1);
+                &self.move_all_cursors_down(height, false);
+            }
+            Event::Shift(Key::Left) => {
+                &self.move_all_cursors_left(true);
+            }
+            Event::Shift(Key::Right) => {
+                &self.move_all_cursors_right(true);
+            }
+            Event::Shift(Key::Up) => {
+                &self.move_all_cursors_up(1, true);
+            }
+            Event::Shift(Key::Down) => {
+                &self.move_all_cursors_down(1, true);
+            }
+            Event::Shift(Key::PageUp) => {
+                let height = cmp::max(self.text_height(), 1);
+                &self.move_all_cursors_up(height, true);
+            }
+            Event::Shift(Key::PageDown) => {
+                let height = cmp::max(self.text_height(), 1);
+                &self.move_all_cursors_down(height, true);
             }
             _ => {
                 debug!("unhandled event (in sly_text_view) {:?}", event);
@@ -380,176 +831,1210 @@ impl SlyTextView {
         let content = self.buffer.borrow_content();
         let rope = content.get_lines();
         let first_offset : usize = rope.line_to_char(self.position.y);
-        let last_offset : usize =
-            rope.line_to_char(self.position.y + self.last_view_size.unwrap().y) - 1;
+        let end_line = cmp::min(self.position.y + self.text_height(), rope.len_lines());
+        let end_offset : usize = rope.line_to_char(end_line);
 
         for c in &self.cursors {
-            if c.0 < first_offset || c.0 > last_offset {
+            if c.offset < first_offset || (c.offset >= end_offset && end_line < rope.len_lines()) {
                 return true;
             }
         }
         false
     }
 
+    // Width of line numbers column, including margin.
+    fn gutter_width(&self) -> usize {
+        let line_count = self.buffer.borrow_content().get_lines().len_lines();
+        line_count.to_string().len() + INDEX_MARGIN
+    }
+
+    // Returns offset of char drawn at given position (relative to view). Clicks on gutter land at
+    // beginning of line, clicks past the end of line (or text) at it's end.
+    fn offset_at_position(&self, pos : Vec2) -> usize {
+        let content = self.buffer.borrow_content();
+        let rope = content.get_lines();
+        let rows = self.display_rows(rope, pos.y + 1);
+        let row = match rows.get(pos.y) {
+            Some(row) => row,
+            None => return rope.len_chars(),
+        };
+
+        let line_begin = rope.line_to_char(row.line);
+        let column = pos.x.saturating_sub(self.gutter_width()) + self.position.x;
+        cmp::min(line_begin + row.char_at_column(column), Self::row_last_offset(rope, row))
+    }
+
+    // Last offset cursor can be put at in given row. End of a row that wraps is drawn in the next
+    // one.
+    fn row_last_offset(rope : &Rope, row : &DisplayRow) -> usize {
+        if row.wraps {
+            rope.line_to_char(row.line) + row.chars.end - 1
+        } else {
+            line_end_without_newline(rope, row.line)
+        }
+    }
+
+    fn on_mouse_event(
+        &mut self,
+        view_offset : Vec2,
+        position : Vec2,
+        event : MouseEvent,
+    ) -> EventResult {
+        let pos = match position.checked_sub(view_offset) {
+            Some(pos) => pos,
+            None => return EventResult::Ignored,
+        };
+        if self.last_view_size.is_none() || pos.y >= self.text_height() {
+            return EventResult::Ignored; // find bar, or outside of view.
+        }
+
+        // termion does not report modifiers of mouse events (alt+click arrives as a plain click), so
+        // cursors are added with a button picked in settings instead.
+        let add_cursor_button = self.settings_ref().add_cursor_button();
+        match event {
+            MouseEvent::Press(button) if button == add_cursor_button => {
+                let offset = self.offset_at_position(pos);
+                self.cursors.push(Cursor::new(offset));
+                self.reduce_cursor_duplicates();
+            }
+            MouseEvent::Press(MouseButton::Left) => {
+                let offset = self.offset_at_position(pos);
+                let interval = Duration::from_millis(MULTI_CLICK_INTERVAL_MS);
+                let clicks = match self.last_click {
+                    Some((time, last_offset, clicks))
+                        if last_offset == offset && time.elapsed() < interval && clicks < 3 =>
+                    {
+                        clicks + 1
+                    }
+                    _ => 1,
+                };
+                self.last_click = Some((Instant::now(), offset, clicks));
+                self.click(offset, clicks);
+            }
+            MouseEvent::Hold(MouseButton::Left) => {
+                let offset = self.offset_at_position(pos);
+                let mut c = self.cursors[0];
+                c.move_to(offset, true);
+                c.preferred_column = None;
+                self.cursors = vec![c];
+                self.make_sure_first_cursor_visible();
+            }
+            MouseEvent::WheelUp => {
+                self.scroll_up(WHEEL_SCROLL_LINES);
+            }
+            MouseEvent::WheelDown => {
+                self.scroll_down(WHEEL_SCROLL_LINES);
+            }
+            MouseEvent::Release(_) => {}
+            _ => return EventResult::Ignored,
+        }
+        EventResult::Consumed(None)
+    }
+
+    // Single click places cursor, double selects word, triple selects line (with newline).
+    fn click(&mut self, offset : usize, clicks : usize) {
+        let cursor = {
+            let content = self.buffer.borrow_content();
+            let rope = content.get_lines();
+            match clicks {
+                2 => match word_range_at(rope, offset) {
+                    Some(range) => Cursor::with_selection(range.start, range.end),
+                    None => Cursor::new(offset),
+                },
+                3 => {
+                    let line = rope.char_to_line(offset);
+                    let end = if line + 1 < rope.len_lines() {
+                        rope.line_to_char(line + 1)
+                    } else {
+                        rope.len_chars()
+                    };
+                    Cursor::with_selection(rope.line_to_char(line), end)
+                }
+                _ => Cursor::new(offset),
+            }
+        };
+        self.cursors = vec![cursor];
+    }
+
     fn make_sure_first_cursor_visible(&mut self) {
-        let y = self.last_view_size.unwrap().y;
-        let offset = self.cursors[0].0;
-        let line = self.buffer.borrow_content().get_lines().char_to_line(offset);
-        if line + 1 > self.position.y + y {
-            self.position.y = line - y + 1;
+        let offset = self.cursors[0].offset;
+        self.make_sure_offset_visible(offset);
+    }
+
+    fn open_find_bar(&mut self) {
+        if self.find_bar.is_some() {
+            return;
         }
 
+        let first = self.cursors[0];
+        // single-line selection is a good guess of what user is looking for, multi-line one is
+        // rather where to look for it.
+        let (query, multiline) = match first.selection() {
+            Some(selection) => {
+                let text = self.buffer.borrow_content().get_lines().slice(selection).to_string();
+                if text.contains('\n') {
+                    (String::new(), true)
+                } else {
+                    (text, false)
+                }
+            }
+            None => (String::new(), false),
+        };
+
+        let mut find_bar = FindBar::new(query, first.begin());
+        if multiline {
+            find_bar.set_scope(Some(self.cursors.clone()));
+        }
+        self.find_bar = Some(find_bar);
+        self.refresh_find_bar();
+        // find bar takes one row, cursor could have been there.
+        if self.last_view_size.is_some() {
+            self.make_sure_first_cursor_visible();
+        }
+    }
+
+    fn refresh_find_bar(&mut self) {
+        if let Some(ref mut find_bar) = self.find_bar {
+            let content = self.buffer.borrow_content();
+            find_bar.refresh(content.get_lines(), content.current_revision_id());
+        }
+    }
+
+    // Returns true if event was consumed by find bar. Events it does not know go to the text.
+    fn find_bar_on_event(&mut self, event : &Event) -> bool {
+        let find_bar_keybindings = self.settings_ref().get_keybindings("find_bar");
+        if let Some(action) = find_bar_keybindings.get(event) {
+            match action.as_str() {
+                "find_next" => {
+                    if self.find_bar.as_ref().unwrap().focus() == FindBarField::Replacement {
+                        self.replace_one();
+                    } else {
+                        self.select_next_match();
+                    }
+                }
+                "find_previous" => {
+                    self.select_previous_match();
+                }
+                "toggle_case_sensitive" => {
+                    self.find_bar.as_mut().unwrap().toggle_case_sensitive();
+                    self.search_incrementally();
+                }
+                "toggle_whole_word" => {
+                    self.find_bar.as_mut().unwrap().toggle_whole_word();
+                    self.search_incrementally();
+                }
+                "toggle_regex" => {
+                    self.find_bar.as_mut().unwrap().toggle_regex();
+                    self.search_incrementally();
+                }
+                "switch_field" => {
+                    self.find_bar.as_mut().unwrap().switch_field();
+                }
+                "replace_one" => {
+                    self.replace_one();
+                }
+                "replace_all" => {
+                    self.replace_all();
+                }
+                "toggle_in_selection" => {
+                    let cursors = self.cursors.clone();
+                    {
+                        let find_bar = self.find_bar.as_mut().unwrap();
+                        if find_bar.scope().is_some() {
+                            find_bar.set_scope(None);
+                        } else {
+                            find_bar.set_scope(Some(cursors));
+                        }
+                    }
+                    self.refresh_find_bar();
+                }
+                "replace_in_files" => {
+                    let find_bar = self.find_bar.as_ref().unwrap();
+                    if !find_bar.query().is_empty() {
+                        let replacement = find_bar.replacement().cloned().unwrap_or_default();
+                        self.channel
+                            .send(IEvent::ShowProjectReplace(
+                                find_bar.query().clone(),
+                                find_bar.options().clone(),
+                                replacement,
+                            ))
+                            .unwrap();
+                    }
+                }
+                "search_results_buffer" => {
+                    let find_bar = self.find_bar.as_ref().unwrap();
+                    if !find_bar.query().is_empty() {
+                        self.channel
+                            .send(IEvent::ShowSearchResults(
+                                find_bar.query().clone(),
+                                find_bar.options().clone(),
+                            ))
+                            .unwrap();
+                    }
+                }
+                "close_find_bar" => {
+                    self.find_bar = None;
+                }
+                _ => return false,
+            };
+            return true;
+        }
+
+        match event {
+            &Event::Char(c) => {
+                self.find_bar.as_mut().unwrap().push_char(c);
+                if self.find_bar.as_ref().unwrap().focus() == FindBarField::Query {
+                    self.search_incrementally();
+                }
+                true
+            }
+            &Event::Key(Key::Backspace) => {
+                self.find_bar.as_mut().unwrap().pop_char();
+                if self.find_bar.as_ref().unwrap().focus() == FindBarField::Query {
+                    self.search_incrementally();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Selects first match after the place search was started from.
+    fn search_incrementally(&mut self) {
+        self.refresh_find_bar();
+        let match_op = {
+            let find_bar = self.find_bar.as_ref().unwrap();
+            find_bar.next_match(find_bar.origin())
+        };
+        if let Some(range) = match_op {
+            self.select_match(range);
+        }
+    }
+
+    fn select_next_match(&mut self) {
+        self.refresh_find_bar();
+        let offset = self.cursors[0].end();
+        let match_op = self.find_bar.as_ref().unwrap().next_match(offset);
+        if let Some(range) = match_op {
+            self.select_match(range);
+        }
+    }
+
+    fn select_previous_match(&mut self) {
+        self.refresh_find_bar();
+        let offset = self.cursors[0].begin();
+        let match_op = self.find_bar.as_ref().unwrap().previous_match(offset);
+        if let Some(range) = match_op {
+            self.select_match(range);
+        }
+    }
+
+    // Replaces current match (the one selected by first cursor) and selects the next one. If no
+    // match is selected, just looks for the next one.
+    fn replace_one(&mut self) {
+        self.refresh_find_bar();
+        let current = match self.cursors[0].selection() {
+            Some(ref range) if self.find_bar.as_ref().unwrap().matches().contains(range) => {
+                range.clone()
+            }
+            _ => {
+                self.select_next_match();
+                return;
+            }
+        };
+
+        let (new_text, next_op) = {
+            let content = self.buffer.borrow_content();
+            let find_bar = self.find_bar.as_ref().unwrap();
+            let new_text = match find_bar.replacement_for(content.get_lines(), &current) {
+                Some(new_text) => new_text,
+                None => return,
+            };
+            (new_text, find_bar.next_match(current.end).filter(|next| *next != current))
+        };
+
+        let events = vec![EditEvent::Change {
+            offset :  current.start,
+            length :  current.end - current.start,
+            content : new_text,
+        }];
+
+        // next match is known in offsets from before the replacement, so it's mapped as well.
+        let cursors_before = self.cursors.clone();
+        self.cursors = match next_op {
+            Some(next) => vec![Cursor::with_selection(next.start, next.end)],
+            None => vec![Cursor::new(current.start)],
+        };
+        map_cursors(&mut self.cursors, &events);
+        self.find_bar.as_mut().unwrap().map_scope(&events);
+        self.submit_events(events, cursors_before, false);
+    }
+
+    // Replaces all matches (in scope, if set) in a single batch.
+    fn replace_all(&mut self) {
+        self.refresh_find_bar();
+        let replacements = {
+            let content = self.buffer.borrow_content();
+            self.find_bar.as_ref().unwrap().all_replacements(content.get_lines())
+        };
+        if replacements.is_empty() {
+            return;
+        }
+
+        debug!("replacing {} matches", replacements.len());
+        let events = replacement_events(replacements);
+        let cursors_before = self.cursors.clone();
+        map_cursors(&mut self.cursors, &events);
+        self.find_bar.as_mut().unwrap().map_scope(&events);
+        self.submit_events(events, cursors_before, false);
+    }
+
+    fn select_match(&mut self, range : Range<usize>) {
+        self.cursors = vec![Cursor::with_selection(range.start, range.end)];
+        if self.last_view_size.is_some() {
+            self.make_sure_first_cursor_visible();
+        }
+    }
+
+    fn draw_find_bar(&self, find_bar : &FindBar, printer : &Printer, width : usize) {
+        let mark = |enabled : bool| if enabled { "x" } else { " " };
+        let options = find_bar.options();
+        let current = self.cursors[0].selection();
+
+        // (row, label, field text, text on the right)
+        let mut rows : Vec<(usize, &str, &String, String)> = vec![(
+            0,
+            " find: ",
+            find_bar.query(),
+            format!(
+                "{}  [{}] case  [{}] word  [{}] regex  [{}] in selection ",
+                find_bar.status(current.as_ref()),
+                mark(options.case_sensitive),
+                mark(options.whole_word),
+                mark(options.regex),
+                mark(find_bar.scope().is_some())
+            ),
+        )];
+        if let Some(replacement) = find_bar.replacement() {
+            rows.push((1, " replace: ", replacement, "".to_string()));
+        }
+
+        let style = self.settings_ref().get_colorstyle(
+            "theme/text_view/primary_text_color",
+            "theme/text_view/find_bar_background_color",
+        );
+        for &(y, label, field, ref right) in &rows {
+            let left = format!("{}{}", label, field);
+            printer.with_color(style, |printer| {
+                printer.print_hline((0, y), width, " ");
+                printer.print((0, y), &left);
+                if left.width() + 1 + right.width() <= width {
+                    printer.print((width - right.width(), y), right);
+                }
+            });
+
+            let focused = match find_bar.focus() {
+                FindBarField::Query => y == 0,
+                FindBarField::Replacement => y == 1,
+            };
+            if focused {
+                printer.with_color(ColorStyle::highlight(), |printer| {
+                    printer.print((left.width(), y), " ");
+                });
+            }
+        }
+    }
+
+    // Status bar takes the last row of view, unless no segments are configured and there is no
+    // message to show.
+    fn status_bar_height(&self) -> usize {
+        let settings = self.settings_ref();
+        let (left, right) = settings.status_bar_segments();
+        if left.is_empty() && right.is_empty() && self.current_message().is_none() {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn current_message(&self) -> Option<(Severity, String)> {
+        self.notifications
+            .borrow()
+            .current(Instant::now())
+            .map(|notification| (notification.severity, notification.text.clone()))
+    }
+
+    fn status_info(&self) -> StatusInfo {
+        let content = self.buffer.borrow_content();
+        let rope = content.get_lines();
+        let offset = self.cursors[0].offset;
+        let line = rope.char_to_line(offset);
+        let char_idx = offset - rope.line_to_char(line);
+        let rows = line_layout::line_rows(rope, line, self.tab_width, 0);
+
+        let file_name = if self.buffer.is_search_results() {
+            Some("<search results>".to_string())
+        } else {
+            self.buffer.get_filename().map(|name| name.to_string_lossy().to_string())
+        };
+
+        StatusInfo {
+            line :        line,
+            column :      rows[0].column_of_char(char_idx),
+            read_only :   self.buffer.is_read_only(),
+            modified :    self.buffer.modified(),
+            out_of_sync : self.buffer.out_of_sync(),
+            file_name :   file_name,
+            syntax :      content.syntax_name().map(|name| name.to_string()),
+            num_cursors : self.cursors.len(),
+            line_ending : status_bar::line_ending(rope),
+            lsp :         self.lsp_status.get(),
+        }
+    }
+
+    fn draw_status_bar(&self, printer : &Printer, width : usize) {
+        let info = self.status_info();
+        let settings = self.settings_ref();
+        let (left, right) = settings.status_bar_segments();
+        let message_op = self.current_message();
+        let line = match message_op {
+            Some((_, ref text)) => status_bar::message_line(text, width),
+            None => status_bar::status_line(&info, left, right, width),
+        };
+
+        let style = settings.get_colorstyle(
+            match message_op {
+                Some((Severity::Info, _)) => "theme/text_view/info_message_color",
+                Some((Severity::Warning, _)) => "theme/text_view/warning_message_color",
+                Some((Severity::Error, _)) => "theme/text_view/error_message_color",
+                None => "theme/text_view/status_bar_text_color",
+            },
+            "theme/text_view/status_bar_background_color",
+        );
+        printer.with_color(style, |printer| {
+            printer.print_hline((0, 0), width, " ");
+            printer.print((0, 0), &line);
+        });
+    }
+
+    // Number of rows available for text, that is view height without the find bar and status bar.
+    fn text_height(&self) -> usize {
+        let height = self.last_view_size.map(|size| size.y).unwrap_or(0);
+        let find_bar_height = self.find_bar.as_ref().map(|find_bar| find_bar.height()).unwrap_or(0);
+        height.saturating_sub(find_bar_height + self.status_bar_height())
+    }
+
+    // Number of columns available for text, that is view width without the gutter.
+    fn text_width(&self) -> usize {
+        let width = self.last_view_size.map(|size| size.x).unwrap_or(0);
+        width.saturating_sub(self.gutter_width())
+    }
+
+    // Width lines are wrapped at, None if soft wrap is off.
+    fn wrap_width(&self) -> Option<usize> {
+        if !self.soft_wrap {
+            return None;
+        }
+        let width = self.text_width();
+        Some(if self.wrap_column > 0 { cmp::min(self.wrap_column, width) } else { width })
+    }
+
+    // Rows of view, beginning with the first visible line. Without soft wrap, every line takes a
+    // single row, also when it does not fit in view.
+    fn display_rows(&self, rope : &Rope, height : usize) -> Vec<DisplayRow> {
+        let wrap_width = self.wrap_width().unwrap_or(0);
+        line_layout::display_rows(rope, self.position.y, self.tab_width, wrap_width, height)
+    }
+
+    // Scrolls view as little as possible to make offset visible, keeping scroll-off margins
+    // (unless view is too small for them).
+    fn make_sure_offset_visible(&mut self, offset : usize) {
+        let height = self.text_height();
+        let width = self.text_width();
+        if height == 0 || width == 0 {
+            return;
+        }
+
+        let (line, pos_in_line, column) = {
+            let content = self.buffer.borrow_content();
+            let rope = content.get_lines();
+            let line = rope.char_to_line(offset);
+            let pos_in_line = offset - rope.line_to_char(line);
+            // without soft wrap, the whole line is a single row.
+            let row = &line_layout::line_rows(rope, line, self.tab_width, 0)[0];
+            (line, pos_in_line, row.column_of_char(pos_in_line))
+        };
+        let (scroll_off, horizontal_scroll_off) = {
+            let settings = self.settings_ref();
+            (settings.scroll_off(), settings.horizontal_scroll_off())
+        };
+
+        let margin = cmp::min(scroll_off, (height - 1) / 2);
+        if let Some(wrap_width) = self.wrap_width() {
+            self.position.x = 0;
+            self.make_sure_wrapped_offset_visible(line, pos_in_line, wrap_width, margin);
+            return;
+        }
+
+        if line + margin + 1 > self.position.y + height {
+            self.position.y = line + margin + 1 - height;
+        }
+        if line < self.position.y + margin {
+            self.position.y = line.saturating_sub(margin);
+        }
+
+        let margin = cmp::min(horizontal_scroll_off, (width - 1) / 2);
+        if column + margin + 1 > self.position.x + width {
+            self.position.x = column + margin + 1 - width;
+        }
+        if column < self.position.x + margin {
+            self.position.x = column.saturating_sub(margin);
+        }
+    }
+
+    // Soft wrap version of the vertical part of make_sure_offset_visible. View still begins with a
+    // whole line, so margins are kept only as far as that allows.
+    fn make_sure_wrapped_offset_visible(
+        &mut self,
+        line : usize,
+        pos_in_line : usize,
+        wrap_width : usize,
+        margin : usize,
+    ) {
+        let height = self.text_height();
+        let tab_width = self.tab_width;
+        let content = self.buffer.borrow_content();
+        let rope = content.get_lines();
+        let row_in_line =
+            row_of_char(&line_layout::line_rows(rope, line, tab_width, wrap_width), pos_in_line);
+        // rows from the beginning of first_line to the one with offset.
+        let rows_above = |first_line : usize| -> usize {
+            let rows_before : usize = (first_line..line)
+                .map(|other_line| {
+                    line_layout::line_rows(rope, other_line, tab_width, wrap_width).len()
+                })
+                .sum();
+            rows_before + row_in_line
+        };
+
+        // every line takes at least one row, so there is no point in starting further.
+        if line >= self.position.y + height {
+            self.position.y = line + 1 - height;
+        }
         if line < self.position.y {
             self.position.y = line;
         }
+        while self.position.y < line && rows_above(self.position.y) + margin + 1 > height {
+            self.position.y += 1;
+        }
+        while self.position.y > 0 && rows_above(self.position.y) < margin {
+            self.position.y -= 1;
+        }
+    }
+
+    // Moves view up, not touching cursors.
+    fn scroll_up(&mut self, lines : usize) {
+        self.position.y = self.position.y.saturating_sub(lines);
+    }
+
+    // Moves view down, not touching cursors. Last line of text stays visible.
+    fn scroll_down(&mut self, lines : usize) {
+        let len_lines = self.buffer.borrow_content().get_lines().len_lines();
+        self.position.y = cmp::min(self.position.y + lines, len_lines - 1);
+    }
+
+    // Scrolls view so the first cursor is in the middle of it.
+    fn center_on_cursor(&mut self) {
+        let line = self.buffer.borrow_content().get_lines().char_to_line(self.cursors[0].offset);
+        self.position.y = line.saturating_sub(self.text_height() / 2);
+        self.make_sure_first_cursor_visible(); // horizontally
     }
 
     // These are work-in-progress implementations.
+    // Replaces selection of every cursor with text, or inserts it where there is no selection.
     fn add_text(&mut self, text : &String) {
-        let mut edit_events : Vec<EditEvent> = self
-            .cursors
-            .iter()
-            .map(|&cursor| EditEvent::Insert { offset : cursor.0, content : text.clone() })
-            .collect();
+        let texts = vec![text.clone(); self.cursors.len()];
+        self.add_texts(texts);
+    }
 
-        let text_len =
-            unicode_segmentation::UnicodeSegmentation::graphemes(text.as_str(), true).count();
+    // Same as add_text, but every cursor gets it's own text. texts.len() has to match number of
+    // cursors.
+    fn add_texts(&mut self, texts : Vec<String>) {
+        assert!(texts.len() == self.cursors.len());
+        let cursors_before = self.cursors.clone();
+
+        // Single typed characters are grouped into one undo step, new line closes the group.
+        let coalesce = texts.iter().all(|text| text.chars().count() == 1 && text.as_str() != "\n")
+            && cursors_before.iter().all(|c| !c.has_selection());
+
+        let mut edit_events : Vec<EditEvent> = Vec::new();
+        for (c, text) in self.cursors.iter().zip(texts.into_iter()) {
+            let (begin, end) = (c.begin(), c.end());
+            if begin == end {
+                edit_events.push(EditEvent::Insert { offset : begin, content : text });
+            } else {
+                edit_events.push(EditEvent::Change {
+                    offset :  begin,
+                    length :  end - begin,
+                    content : text,
+                });
+            }
+        }
 
+        // Cursors are sorted, so going from the last one keeps offsets of remaining events valid.
         edit_events.reverse();
-        self.submit_events(edit_events);
-        self.mod_all_cursors(text_len as isize);
+        // after mapping, selections collapse right after the texts that replaced them.
+        map_cursors(&mut self.cursors, &edit_events);
+        for c in &mut self.cursors {
+            c.clear_selection();
+        }
+        self.submit_events(edit_events, cursors_before, coalesce);
     }
 
-    fn move_cursor_to_line(rope : &Rope, c : &mut Cursor, other_line : usize) {
-        // let rope = &self.buffer.content().get_lines();
-        assert!(other_line < rope.len_lines());
-        let line = rope.char_to_line(c.0);
-        let pos_in_line = c.0 - rope.line_to_char(line);
-        if c.1.is_some() {
-            assert!(c.1.unwrap() > pos_in_line); //the whole point in tracking that is to be able to shift back right
+    // Ranges copied or cut by every cursor: it's selection, or whole line if there is none.
+    fn copy_ranges(&self) -> Vec<Range<usize>> {
+        let content = self.buffer.borrow_content();
+        let rope = content.get_lines();
+        self.cursors
+            .iter()
+            .map(|c| match c.selection() {
+                Some(selection) => selection,
+                None => {
+                    let line = rope.char_to_line(c.offset);
+                    let end = if line + 1 < rope.len_lines() {
+                        rope.line_to_char(line + 1)
+                    } else {
+                        rope.len_chars()
+                    };
+                    rope.line_to_char(line)..end
+                }
+            })
+            .collect()
+    }
+
+    // Puts pieces on the kill ring and in clipboard.
+    fn store_pieces(&mut self, pieces : Vec<String>) {
+        if let Err(e) = (*self.clipboard).borrow_mut().set_contents(join_pieces(&pieces)) {
+            let text = format!("unable to write to clipboard, because \"{}\"", e);
+            self.channel.send(IEvent::Notify(Severity::Warning, text)).unwrap();
         }
+        (*self.kill_ring).borrow_mut().push(pieces);
+    }
 
-        let effective_pos_in_line = match c.1 {
-            Some(pos) => pos,
-            None => pos_in_line,
+    fn copy(&mut self) {
+        let pieces : Vec<String> = {
+            let content = self.buffer.borrow_content();
+            let rope = content.get_lines();
+            self.copy_ranges().into_iter().map(|range| rope.slice(range).to_string()).collect()
+        };
+        debug!("copied {} pieces", pieces.len());
+        self.store_pieces(pieces);
+    }
+
+    fn cut(&mut self) {
+        let ranges = self.copy_ranges();
+        let pieces : Vec<String> = {
+            let content = self.buffer.borrow_content();
+            let rope = content.get_lines();
+            ranges.iter().map(|range| rope.slice(range.clone()).to_string()).collect()
+        };
+        debug!("cut {} pieces", pieces.len());
+        self.store_pieces(pieces);
+        self.remove_ranges(ranges, false);
+    }
+
+    // System clipboard takes precedence, unless it holds exactly what was last put on kill ring -
+    // then pieces from kill ring are used, so each cursor gets back what it copied.
+    fn paste(&mut self) {
+        let clipboard_op : Option<String> = (*self.clipboard).borrow_mut().get_contents();
+        let top_op : Option<Vec<String>> = self.kill_ring.borrow().top().cloned();
+
+        let pieces : Vec<String> = match (clipboard_op, top_op) {
+            (Some(text), Some(top)) => {
+                if join_pieces(&top) == text {
+                    top
+                } else {
+                    vec![text]
+                }
+            }
+            (Some(text), None) => vec![text],
+            (None, Some(top)) => top,
+            (None, None) => {
+                debug!("nothing to paste");
+                return;
+            }
+        };
+
+        self.paste_pieces(pieces);
+        debug!("pasted");
+    }
+
+    /// Pastes one piece per cursor if their numbers match, otherwise all pieces joined are pasted
+    /// at every cursor.
+    pub fn paste_pieces(&mut self, pieces : Vec<String>) {
+        if pieces.len() == self.cursors.len() {
+            self.add_texts(pieces);
+        } else {
+            self.add_text(&join_pieces(&pieces));
+        }
+    }
+
+    // Moves cursor count rows of view up or down, keeping it's column (or the preferred one) as far
+    // as rows allow. Without soft wrap, rows are just lines. From the first (last) row, goes to the
+    // beginning (end) of buffer instead, returning false.
+    fn move_cursor_by_rows(
+        rope : &Rope,
+        c : &mut Cursor,
+        tab_width : usize,
+        wrap_width : usize,
+        count : usize,
+        up : bool,
+    ) -> bool {
+        let mut line = rope.char_to_line(c.offset);
+        let mut rows = line_layout::line_rows(rope, line, tab_width, wrap_width);
+        let pos_in_line = c.offset - rope.line_to_char(line);
+        let mut row_idx = row_of_char(&rows, pos_in_line);
+        let x = match c.preferred_column {
+            Some(x) => x,
+            None => rows[row_idx].column_of_char(pos_in_line),
+        };
+
+        let at_edge = if up {
+            line == 0 && row_idx == 0
+        } else {
+            line + 1 == rope.len_lines() && row_idx + 1 == rows.len()
         };
-        c.1 = None;
+        if at_edge {
+            c.offset = if up { 0 } else { rope.len_chars() };
+            c.preferred_column = None;
+            return false;
+        }
 
-        let pos_in_line_below = cmp::min(rope.line(other_line).len_chars(), effective_pos_in_line);
-        if effective_pos_in_line > pos_in_line_below {
-            c.1 = Some(effective_pos_in_line);
+        for _ in 0..count {
+            if up && row_idx > 0 {
+                row_idx -= 1;
+            } else if up && line > 0 {
+                line -= 1;
+                rows = line_layout::line_rows(rope, line, tab_width, wrap_width);
+                row_idx = rows.len() - 1;
+            } else if !up && row_idx + 1 < rows.len() {
+                row_idx += 1;
+            } else if !up && line + 1 < rope.len_lines() {
+                line += 1;
+                rows = line_layout::line_rows(rope, line, tab_width, wrap_width);
+                row_idx = 0;
+            } else {
+                break;
+            }
         }
 
-        c.0 = rope.line_to_char(other_line) + pos_in_line_below;
+        let row = &rows[row_idx];
+        let line_begin = rope.line_to_char(line);
+        c.offset = cmp::min(line_begin + row.char_at_column(x), Self::row_last_offset(rope, row));
+        // the whole point in tracking that is to be able to shift back right.
+        c.preferred_column =
+            if row.column_of_char(c.offset - line_begin) < x { Some(x) } else { None };
+        true
     }
 
-    fn move_all_cursors_up(&mut self, len : usize) {
+    // Starts selection if extend is true and there is none, drops it otherwise.
+    fn prepare_selection(c : &mut Cursor, extend : bool) {
+        let offset = c.offset;
+        c.move_to(offset, extend);
+    }
+
+    fn move_all_cursors_up(&mut self, len : usize, extend : bool) {
+        self.move_all_cursors_by_rows(len, extend, true);
+    }
+
+    fn move_all_cursors_down(&mut self, len : usize, extend : bool) {
+        self.move_all_cursors_by_rows(len, extend, false);
+    }
+
+    fn move_all_cursors_by_rows(&mut self, len : usize, extend : bool, up : bool) {
         assert!(len > 0);
+        let (tab_width, wrap_width) = (self.tab_width, self.wrap_width().unwrap_or(0));
         {
             let content = self.buffer.borrow_content();
             let rope : &Rope = content.get_lines();
             for mut c in &mut self.cursors {
-                let line = rope.char_to_line(c.0);
-                if line == 0 {
-                    c.0 = 0;
-                    c.1 = None;
+                Self::prepare_selection(c, extend);
+                Self::move_cursor_by_rows(rope, &mut c, tab_width, wrap_width, len, up);
+            }
+        }
+        self.reduce_cursor_duplicates();
+        self.make_sure_first_cursor_visible();
+    }
+
+    // Without extend, a cursor with selection just lands on the beginning of it.
+    fn move_all_cursors_left(&mut self, extend : bool) {
+        {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            for c in &mut self.cursors {
+                let new_offset = if !extend && c.has_selection() {
+                    c.begin()
                 } else {
-                    let next_line_no = if line > len { line - len } else { 0 };
-                    Self::move_cursor_to_line(rope, &mut c, next_line_no);
-                }
+                    text_navigation::grapheme_start_before(rope, c.offset)
+                };
+                c.move_to(new_offset, extend);
+                c.preferred_column = None;
             }
         }
+        self.reduce_cursor_duplicates();
         self.make_sure_first_cursor_visible();
     }
 
-    fn move_all_cursors_down(&mut self, len : usize) {
-        assert!(len > 0);
+    // Without extend, a cursor with selection just lands on the end of it.
+    fn move_all_cursors_right(&mut self, extend : bool) {
         {
             let content = self.buffer.borrow_content();
             let rope : &Rope = content.get_lines();
-            for mut c in &mut self.cursors {
-                let line = rope.char_to_line(c.0);
-                if line == rope.len_lines() - 1 {
-                    c.0 = rope.len_chars() - 1;
-                    c.1 = None;
+            for c in &mut self.cursors {
+                let new_offset = if !extend && c.has_selection() {
+                    c.end()
                 } else {
-                    let next_line_no = cmp::min(rope.len_lines() - 1, line + len);
-                    Self::move_cursor_to_line(rope, &mut c, next_line_no);
-                }
+                    text_navigation::grapheme_end_after(rope, c.offset)
+                };
+                c.move_to(new_offset, extend);
+                c.preferred_column = None;
             }
         }
+        self.reduce_cursor_duplicates();
         self.make_sure_first_cursor_visible();
     }
 
-    fn move_all_cursors_left(&mut self) {
-        let content = self.buffer.borrow_content();
-        let rope = content.get_lines();
-        for c in &mut self.cursors {
-            if c.0 > 0 {
-                c.0 -= 1;
+    // Moves every cursor to offset computed by f from it's current one.
+    fn move_all_cursors_with<F>(&mut self, f : F, extend : bool)
+    where
+        F : Fn(&Rope, usize) -> usize,
+    {
+        {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            for c in &mut self.cursors {
+                let new_offset = f(rope, c.offset);
+                c.move_to(new_offset, extend);
+                c.preferred_column = None;
+            }
+        }
+        self.reduce_cursor_duplicates();
+        self.make_sure_first_cursor_visible();
+    }
+
+    fn select_all(&mut self) {
+        let len_chars = self.buffer.borrow_content().get_lines().len_chars();
+        self.cursors = vec![Cursor::with_selection(0, len_chars)];
+    }
+
+    fn reduce_cursor_duplicates(&mut self) {
+        normalize_cursors(&mut self.cursors);
+    }
+
+    // Adds a cursor one line above the first one, in the same column (as far as line allows).
+    fn add_cursor_above(&mut self) {
+        let new_cursor_op = {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            let mut c = Cursor::new(self.cursors[0].offset);
+            if !self.soft_wrap {
+                c.preferred_column = self.cursors[0].preferred_column;
             }
-            if c.1.is_some() {
-                c.1 = None;
+            // lines, not rows: there is no wrap width.
+            if Self::move_cursor_by_rows(rope, &mut c, self.tab_width, 0, 1, true) {
+                Some(c)
+            } else {
+                None
             }
+        };
+
+        if let Some(c) = new_cursor_op {
+            self.cursors.push(c);
+            self.reduce_cursor_duplicates();
+            self.make_sure_offset_visible(c.offset);
         }
     }
 
-    fn move_all_cursors_right(&mut self) {
-        let content = self.buffer.borrow_content();
-        let rope = content.get_lines();
-        for c in &mut self.cursors {
-            if c.0 < rope.len_chars() {
-                c.0 += 1;
+    // Adds a cursor one line below the last one, in the same column (as far as line allows).
+    fn add_cursor_below(&mut self) {
+        let new_cursor_op = {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            let last = self.cursors.last().unwrap();
+            let mut c = Cursor::new(last.offset);
+            if !self.soft_wrap {
+                c.preferred_column = last.preferred_column;
             }
-            if c.1.is_some() {
-                c.1 = None;
+            if Self::move_cursor_by_rows(rope, &mut c, self.tab_width, 0, 1, false) {
+                Some(c)
+            } else {
+                None
             }
+        };
+
+        if let Some(c) = new_cursor_op {
+            self.cursors.push(c);
+            self.reduce_cursor_duplicates();
+            self.make_sure_offset_visible(c.offset);
         }
     }
 
-    fn reduce_cursor_duplicates(&mut self) {
-        self.cursors.dedup_by(|a, b| a.0 == b.0);
+    // Text that occurrence commands look for: selection of the last cursor. If there is no
+    // selection, cursors without one select words under them first, and None is returned.
+    fn occurrence_pattern(&mut self) -> Option<String> {
+        if let Some(selection) = self.cursors.last().unwrap().selection() {
+            let content = self.buffer.borrow_content();
+            return Some(content.get_lines().slice(selection).to_string());
+        }
+
+        {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            for c in &mut self.cursors {
+                if !c.has_selection() {
+                    if let Some(word) = word_range_at(rope, c.offset) {
+                        *c = Cursor::with_selection(word.start, word.end);
+                    }
+                }
+            }
+        }
+        self.reduce_cursor_duplicates();
+        None
     }
 
-    fn backspace(&mut self) {
-        let mut edit_events : Vec<EditEvent> = self
-            .cursors
-            .iter()
-            .filter(|&cursor| cursor.0 > 0)
-            .map(|&cursor| EditEvent::Change {
-                offset :  cursor.0 - 1,
-                length :  1,
-                content : "".to_string(),
+    // Returns char ranges of all non-overlapping occurrences of pattern.
+    fn find_occurrences(&self, pattern : &String) -> Vec<Range<usize>> {
+        let content = self.buffer.borrow_content();
+        let rope : &Rope = content.get_lines();
+        let pattern_len = pattern.chars().count();
+        let text = rope.to_string();
+        text.match_indices(pattern.as_str())
+            .map(|(byte_idx, _)| {
+                let begin = rope.byte_to_char(byte_idx);
+                begin..(begin + pattern_len)
             })
-            .collect();
+            .collect()
+    }
 
-        self.mod_all_cursors(-1);
+    // Selects next occurrence of last cursor's selection (wrapping around end of file) with a new
+    // cursor. Without selection, just selects words under cursors.
+    fn add_next_occurrence(&mut self) {
+        let pattern = match self.occurrence_pattern() {
+            Some(pattern) => pattern,
+            None => return,
+        };
 
-        edit_events.reverse();
-        self.submit_events(edit_events);
+        let last_end = self.cursors.last().unwrap().end();
+        let occurrences = self.find_occurrences(&pattern);
+        let next_op = {
+            let free = |range : &&Range<usize>| {
+                !self.cursors.iter().any(|c| c.begin() < range.end && range.start < c.end())
+            };
+            let after = occurrences.iter().filter(|r| r.start >= last_end).find(&free);
+            after.or_else(|| occurrences.iter().find(&free)).cloned()
+        };
+
+        match next_op {
+            Some(range) => {
+                self.cursors.push(Cursor::with_selection(range.start, range.end));
+                self.reduce_cursor_duplicates();
+                self.make_sure_offset_visible(range.end);
+            }
+            None => debug!("no more occurrences of {:?}", pattern),
+        }
     }
 
-    fn mod_all_cursors(&mut self, diff : isize) {
-        for (i, c) in self.cursors.iter_mut().enumerate() {
-            if diff > 0 {
-                c.0 += (diff as usize * (i + 1));
-            } else {
-                let pdiff = (diff * -1) as usize;
-                c.0 -= cmp::min(c.0, pdiff * (i + 1));
+    // Replaces all cursors with selections of every occurrence of last cursor's selection (or word
+    // under it).
+    fn select_all_occurrences(&mut self) {
+        let pattern = match self.occurrence_pattern() {
+            Some(pattern) => pattern,
+            None => {
+                let selection = self.cursors.last().unwrap().selection();
+                match selection {
+                    Some(selection) => {
+                        let content = self.buffer.borrow_content();
+                        content.get_lines().slice(selection).to_string()
+                    }
+                    None => return,
+                }
             }
-            c.1 = None;
+        };
+
+        let occurrences = self.find_occurrences(&pattern);
+        if occurrences.is_empty() {
+            return;
         }
+
+        self.cursors = occurrences
+            .into_iter()
+            .map(|range| Cursor::with_selection(range.start, range.end))
+            .collect();
         self.reduce_cursor_duplicates();
     }
 
+    // Every selection spanning multiple lines is replaced with one cursor per line, each
+    // selecting it's part of the line. Line ends are not selected.
+    fn split_selection_into_lines(&mut self) {
+        let mut new_cursors : Vec<Cursor> = Vec::new();
+        {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            for c in &self.cursors {
+                let selection = match c.selection() {
+                    Some(selection) => selection,
+                    None => {
+                        new_cursors.push(*c);
+                        continue;
+                    }
+                };
+
+                let first_line = rope.char_to_line(selection.start);
+                let last_line = rope.char_to_line(selection.end);
+                for line in first_line..(last_line + 1) {
+                    let line_begin = rope.line_to_char(line);
+                    // selection ending at the beginning of line does not include that line.
+                    if line > first_line && line_begin == selection.end {
+                        break;
+                    }
+
+                    let begin = cmp::max(line_begin, selection.start);
+                    let end = cmp::min(line_end_without_newline(rope, line), selection.end);
+                    new_cursors.push(if begin < end {
+                        Cursor::with_selection(begin, end)
+                    } else {
+                        Cursor::new(begin)
+                    });
+                }
+            }
+        }
+
+        self.cursors = new_cursors;
+        self.reduce_cursor_duplicates();
+    }
+
+    // Removes selection of every cursor, or a character before it where there is no selection.
+    fn backspace(&mut self) {
+        let ranges : Vec<Range<usize>> = {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            self.cursors
+                .iter()
+                .map(|c| {
+                    if c.has_selection() {
+                        c.begin()..c.end()
+                    } else {
+                        text_navigation::grapheme_start_before(rope, c.offset)..c.offset
+                    }
+                })
+                .collect()
+        };
+        let coalesce = self.cursors.iter().all(|c| !c.has_selection());
+        self.remove_ranges(ranges, coalesce);
+    }
+
+    // Runs one of line_operations, submitting it's events as a single batch.
+    fn apply_line_operation<F>(&mut self, f : F)
+    where
+        F : Fn(&Rope, &Vec<Cursor>, &str) -> LineOperationResult,
+    {
+        let indent_string = self.settings_ref().indent_string().clone();
+        let result = {
+            let content = self.buffer.borrow_content();
+            f(content.get_lines(), &self.cursors, &indent_string)
+        };
+
+        if let Some((events, mut new_cursors)) = result {
+            let cursors_before = self.cursors.clone();
+            normalize_cursors(&mut new_cursors);
+            self.cursors = new_cursors;
+            self.submit_events(events, cursors_before, false);
+        }
+    }
+
+    // Removes selection of every cursor, or where there is none, text between cursor and offset
+    // computed by f from it.
+    fn delete_with<F>(&mut self, f : F)
+    where
+        F : Fn(&Rope, usize) -> usize,
+    {
+        let ranges : Vec<Range<usize>> = {
+            let content = self.buffer.borrow_content();
+            let rope : &Rope = content.get_lines();
+            self.cursors
+                .iter()
+                .map(|c| match c.selection() {
+                    Some(selection) => selection,
+                    None => {
+                        let target = f(rope, c.offset);
+                        cmp::min(target, c.offset)..cmp::max(target, c.offset)
+                    }
+                })
+                .collect()
+        };
+        self.remove_ranges(ranges, false);
+    }
+
+    // Removes one range per cursor, leaving cursor where it's range was. Ranges are expected in
+    // cursor order, overlapping parts are removed only once.
+    fn remove_ranges(&mut self, ranges : Vec<Range<usize>>, coalesce : bool) {
+        assert!(ranges.len() == self.cursors.len());
+        let cursors_before = self.cursors.clone();
+
+        let mut edit_events : Vec<EditEvent> = Vec::new();
+        let mut last_end : usize = 0;
+        for (c, range) in self.cursors.iter_mut().zip(ranges.into_iter()) {
+            let end = range.end;
+            let begin = cmp::min(cmp::max(range.start, last_end), end);
+            last_end = cmp::max(last_end, end);
+
+            if begin != end {
+                edit_events.push(EditEvent::Change {
+                    offset :  begin,
+                    length :  end - begin,
+                    content : "".to_string(),
+                });
+            }
+
+            *c = Cursor::new(end);
+        }
+
+        if edit_events.is_empty() {
+            self.cursors = cursors_before;
+            return;
+        }
+
+        edit_events.reverse();
+        map_cursors(&mut self.cursors, &edit_events);
+        self.submit_events(edit_events, cursors_before, coalesce);
+    }
+
+    fn had_match_at(&self, offset : usize) -> bool {
+        match self.find_bar {
+            Some(ref find_bar) => find_bar.is_match_at(offset),
+            None => false,
+        }
+    }
+
     fn had_cursor_at(&self, offset : &usize) -> bool {
         for ref c in &self.cursors {
-            if c.0 == *offset {
+            if c.offset == *offset {
                 return true;
             }
         }
         false
     }
+
+    fn had_selection_at(&self, offset : &usize) -> bool {
+        for ref c in &self.cursors {
+            if let Some(selection) = c.selection() {
+                if selection.start <= *offset && *offset < selection.end {
+                    return true;
+                }
+            }
+        }
+        false
+    }
 }
 
 // #[cfg(test)]
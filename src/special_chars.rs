@@ -0,0 +1,113 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Chars text view draws with a glyph from settings instead of themselves. Whitespace is shown
+// depending on WhitespaceMode, carriage returns and other control chars always, as printing them
+// would break the terminal.
+
+/// When whitespace is drawn with glyphs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WhitespaceMode {
+    Always,
+    Selection, // only inside of selections
+    Never,
+}
+
+impl WhitespaceMode {
+    /// Parses mode as written in settings: "always", "selection" or "never".
+    pub fn from_name(name : &str) -> Option<WhitespaceMode> {
+        match name {
+            "always" => Some(WhitespaceMode::Always),
+            "selection" => Some(WhitespaceMode::Selection),
+            "never" => Some(WhitespaceMode::Never),
+            _ => None,
+        }
+    }
+
+    pub fn shows(&self, selected : bool) -> bool {
+        match self {
+            &WhitespaceMode::Always => true,
+            &WhitespaceMode::Selection => selected,
+            &WhitespaceMode::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpecialChar {
+    Space,
+    Tab,
+    NonBreakingSpace,
+    Newline, // "\n" and "\r\n"
+    CarriageReturn,
+    Control,
+}
+
+impl SpecialChar {
+    /// Classifies grapheme cluster, None meaning it's drawn as it is.
+    pub fn of(grapheme : &str) -> Option<SpecialChar> {
+        match grapheme {
+            " " => Some(SpecialChar::Space),
+            "\t" => Some(SpecialChar::Tab),
+            "\u{a0}" | "\u{202f}" => Some(SpecialChar::NonBreakingSpace),
+            "\n" | "\r\n" => Some(SpecialChar::Newline),
+            "\r" => Some(SpecialChar::CarriageReturn),
+            _ if grapheme.chars().any(|c| c.is_control()) => Some(SpecialChar::Control),
+            _ => None,
+        }
+    }
+
+    /// Whitespace is drawn with glyph depending on WhitespaceMode, the rest always.
+    pub fn is_whitespace(&self) -> bool {
+        match self {
+            &SpecialChar::CarriageReturn | &SpecialChar::Control => false,
+            _ => true,
+        }
+    }
+
+    /// Returns settings selector of glyph. Trailing spaces and tabs have a glyph of their own.
+    pub fn glyph_selector(&self, trailing : bool) -> &'static str {
+        match self {
+            &SpecialChar::Space if trailing => "whitespace/trailing",
+            &SpecialChar::Space => "whitespace/space",
+            &SpecialChar::Tab => "whitespace/tab",
+            &SpecialChar::NonBreakingSpace => "whitespace/non_breaking_space",
+            &SpecialChar::Newline => "whitespace/newline",
+            &SpecialChar::CarriageReturn => "whitespace/carriage_return",
+            &SpecialChar::Control => "whitespace/control",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graphemes_are_classified() {
+        assert_eq!(SpecialChar::of(" "), Some(SpecialChar::Space));
+        assert_eq!(SpecialChar::of("\r\n"), Some(SpecialChar::Newline));
+        assert_eq!(SpecialChar::of("\r"), Some(SpecialChar::CarriageReturn));
+        assert_eq!(SpecialChar::of("\u{1b}"), Some(SpecialChar::Control));
+        assert_eq!(SpecialChar::of("a"), None);
+        assert_eq!(SpecialChar::of("\u{4e2d}"), None);
+
+        assert!(WhitespaceMode::Selection.shows(true));
+        assert!(!WhitespaceMode::Selection.shows(false));
+        assert_eq!(WhitespaceMode::from_name("always"), Some(WhitespaceMode::Always));
+        assert_eq!(WhitespaceMode::from_name("sometimes"), None);
+    }
+}
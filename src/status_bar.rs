@@ -0,0 +1,179 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Status line drawn under every text view. It's built of segments listed in settings
+// ("status_bar/left" and "status_bar/right"), each one rendered from StatusInfo gathered by view.
+// While there is a message for the user, it takes the whole line, so it's not cut in favour of
+// segments on a narrow view.
+// Known segments: "position", "mode", "modified", "out_of_sync", "file_name", "syntax",
+// "cursors", "line_ending" and "lsp". Segments with nothing to say (like "modified" of unmodified
+// buffer) are skipped.
+
+use lsp_client::LspStatus;
+use ropey::Rope;
+use unicode_width::UnicodeWidthChar;
+use unicode_width::UnicodeWidthStr;
+
+const SEPARATOR : &'static str = "  ";
+
+#[derive(Clone, Debug)]
+pub struct StatusInfo {
+    pub line :        usize, // of the first cursor, 0 based
+    pub column :      usize, // screen column, 0 based
+    pub read_only :   bool,
+    pub modified :    bool,
+    pub out_of_sync : bool,
+    pub file_name :   Option<String>,
+    pub syntax :      Option<String>,
+    pub num_cursors : usize,
+    pub line_ending : &'static str,
+    pub lsp :         LspStatus,
+}
+
+/// Returns name of line ending used in text, judging by the first line. Text without line
+/// endings is assumed to use "LF".
+pub fn line_ending(rope : &Rope) -> &'static str {
+    if rope.len_lines() < 2 {
+        return "LF";
+    }
+
+    let line = rope.line(0);
+    let len = line.len_chars();
+    if len > 1 && line.char(len - 2) == '\r' && line.char(len - 1) == '\n' {
+        "CRLF"
+    } else if line.char(len - 1) == '\r' {
+        "CR"
+    } else {
+        "LF"
+    }
+}
+
+// Returns text of segment, None if it's empty or unknown.
+fn segment(name : &str, info : &StatusInfo) -> Option<String> {
+    match name {
+        "position" => Some(format!("Ln {}, Col {}", info.line + 1, info.column + 1)),
+        "mode" => Some(if info.read_only { "RO" } else { "RW" }.to_string()),
+        "modified" if info.modified => Some("[+]".to_string()),
+        "out_of_sync" if info.out_of_sync => Some("[changed on disk]".to_string()),
+        "file_name" => Some(info.file_name.clone().unwrap_or_else(|| "<unnamed>".to_string())),
+        "syntax" => Some(info.syntax.clone().unwrap_or_else(|| "Plain Text".to_string())),
+        "cursors" if info.num_cursors > 1 => Some(format!("{} cursors", info.num_cursors)),
+        "line_ending" => Some(info.line_ending.to_string()),
+        "lsp" => match info.lsp {
+            LspStatus::Off => None,
+            LspStatus::Starting => Some("LSP: starting".to_string()),
+            LspStatus::Ready => Some("LSP: ready".to_string()),
+            LspStatus::Failed => Some("LSP: failed".to_string()),
+        },
+        "modified" | "out_of_sync" | "cursors" => None,
+        _ => {
+            debug!("unknown status bar segment {:?}", name);
+            None
+        }
+    }
+}
+
+fn join_segments(names : &[String], info : &StatusInfo) -> String {
+    let segments : Vec<String> = names.iter().filter_map(|name| segment(name, info)).collect();
+    segments.join(SEPARATOR)
+}
+
+// Returns longest prefix of text not wider than width.
+fn truncate(text : &str, width : usize) -> String {
+    let mut result = String::new();
+    let mut result_width : usize = 0;
+    for c in text.chars() {
+        result_width += c.width().unwrap_or(0);
+        if result_width > width {
+            break;
+        }
+        result.push(c);
+    }
+    result
+}
+
+// Returns line exactly width columns wide, left part gets cut first.
+fn aligned(left : &str, right : &str, width : usize) -> String {
+    let right_text = truncate(&format!("{} ", right), width);
+    let left_width = width - right_text.width();
+    let left_text = truncate(&format!(" {}", left), left_width);
+    let padding = " ".repeat(left_width - left_text.width());
+    format!("{}{}{}", left_text, padding, right_text)
+}
+
+/// Returns status line exactly width columns wide: left segments aligned to the left, right ones
+/// to the right. Left part gets cut first.
+pub fn status_line(
+    info : &StatusInfo,
+    left : &[String],
+    right : &[String],
+    width : usize,
+) -> String {
+    aligned(&join_segments(left, info), &join_segments(right, info), width)
+}
+
+/// Returns status line exactly width columns wide, with first line of message in place of all
+/// segments.
+pub fn message_line(message : &str, width : usize) -> String {
+    let text = truncate(&format!(" {}", message.lines().next().unwrap_or("")), width);
+    let padding = " ".repeat(width - text.width());
+    format!("{}{}", text, padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> StatusInfo {
+        StatusInfo {
+            line :        11,
+            column :      4,
+            read_only :   false,
+            modified :    true,
+            out_of_sync : false,
+            file_name :   Some("main.rs".to_string()),
+            syntax :      Some("Rust".to_string()),
+            num_cursors : 1,
+            line_ending : "LF",
+            lsp :         LspStatus::Off,
+        }
+    }
+
+    fn names(names : &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn segments_are_aligned_and_empty_ones_skipped() {
+        let left = names(&["mode", "modified", "out_of_sync", "file_name"]);
+        let right = names(&["position", "cursors", "lsp"]);
+
+        let line = status_line(&info(), &left, &right, 40);
+        assert_eq!(line, " RW  [+]  main.rs          Ln 12, Col 5 ");
+        assert_eq!(status_line(&info(), &left, &right, 10), "Ln 12, Col");
+
+        let line = message_line("file save failed, because \"disk full\"\nsecond line", 32);
+        assert_eq!(line, " file save failed, because \"disk");
+        assert_eq!(message_line("saved", 10), " saved    ");
+    }
+
+    #[test]
+    fn line_ending_is_detected_by_first_line() {
+        assert_eq!(line_ending(&Rope::from_str("a\r\nb\n")), "CRLF");
+        assert_eq!(line_ending(&Rope::from_str("a\nb\r\n")), "LF");
+        assert_eq!(line_ending(&Rope::from_str("a")), "LF");
+    }
+}
@@ -0,0 +1,261 @@
+/*
+Copyright 2018 Google LLC
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    https://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+// Functions computing where cursor should go, given it's current offset. All offsets are in CHARS.
+// Word movement uses unicode word bounds within a line, skipping whitespace segments. Line ends
+// are treated as a separate stop. Char movement goes by grapheme clusters.
+
+use line_layout::line_graphemes;
+use ropey::Rope;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+fn is_word_char(c : char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns range of word touching offset, if any.
+pub fn word_range_at(rope : &Rope, offset : usize) -> Option<Range<usize>> {
+    let len_chars = rope.len_chars();
+    let touches_word_after = offset < len_chars && is_word_char(rope.char(offset));
+    let touches_word_before = offset > 0 && is_word_char(rope.char(offset - 1));
+    if !touches_word_after && !touches_word_before {
+        return None;
+    }
+
+    let mut begin = offset;
+    while begin > 0 && is_word_char(rope.char(begin - 1)) {
+        begin -= 1;
+    }
+    let mut end = offset;
+    while end < len_chars && is_word_char(rope.char(end)) {
+        end += 1;
+    }
+    Some(begin..end)
+}
+
+// Chars rope breaks lines at ("\r\n" counts as one break).
+fn is_line_break(c : char) -> bool {
+    match c {
+        '\n' | '\u{000B}' | '\u{000C}' | '\r' | '\u{0085}' | '\u{2028}' | '\u{2029}' => true,
+        _ => false,
+    }
+}
+
+/// Returns offset right after the last char of line, not counting the line break.
+pub fn line_end_without_newline(rope : &Rope, line : usize) -> usize {
+    let line_slice = rope.line(line);
+    let mut len = line_slice.len_chars();
+    if len > 0 && is_line_break(line_slice.char(len - 1)) {
+        len -= 1;
+        if len > 0 && line_slice.char(len) == '\n' && line_slice.char(len - 1) == '\r' {
+            len -= 1;
+        }
+    }
+    rope.line_to_char(line) + len
+}
+
+// Returns (begin, end, is_blank) of every word bound segment of line, in chars relative to line
+// beginning.
+fn line_segments(rope : &Rope, line : usize) -> Vec<(usize, usize, bool)> {
+    let line_string = rope.line(line).to_string();
+    let mut result : Vec<(usize, usize, bool)> = Vec::new();
+    let mut begin : usize = 0;
+    for segment in line_string.split_word_bounds() {
+        let end = begin + segment.chars().count();
+        result.push((begin, end, segment.chars().all(|c| c.is_whitespace())));
+        begin = end;
+    }
+    result
+}
+
+/// Beginning of the grapheme cluster before offset.
+pub fn grapheme_start_before(rope : &Rope, offset : usize) -> usize {
+    if offset == 0 {
+        return 0;
+    }
+
+    // graphemes never span lines, but "\r\n" is a single one.
+    let line = rope.char_to_line(offset - 1);
+    let line_begin = rope.line_to_char(line);
+    let pos = offset - line_begin;
+    line_graphemes(rope, line, None)
+        .into_iter()
+        .rev()
+        .find(|grapheme| grapheme.chars.start < pos)
+        .map(|grapheme| line_begin + grapheme.chars.start)
+        .unwrap_or(offset - 1)
+}
+
+/// End of the grapheme cluster after offset.
+pub fn grapheme_end_after(rope : &Rope, offset : usize) -> usize {
+    let len_chars = rope.len_chars();
+    if offset >= len_chars {
+        return len_chars;
+    }
+
+    let line = rope.char_to_line(offset);
+    let line_begin = rope.line_to_char(line);
+    let pos = offset - line_begin;
+    line_graphemes(rope, line, None)
+        .into_iter()
+        .find(|grapheme| grapheme.chars.end > pos)
+        .map(|grapheme| line_begin + grapheme.chars.end)
+        .unwrap_or(offset + 1)
+}
+
+/// Beginning of the word before offset. From the beginning of line, goes to the end of previous
+/// one.
+pub fn word_start_before(rope : &Rope, offset : usize) -> usize {
+    if offset == 0 {
+        return 0;
+    }
+
+    let line = rope.char_to_line(offset);
+    let line_begin = rope.line_to_char(line);
+    if offset == line_begin {
+        return line_end_without_newline(rope, line - 1);
+    }
+
+    let pos = offset - line_begin;
+    let mut result : usize = 0;
+    for (begin, _, is_blank) in line_segments(rope, line) {
+        if begin >= pos {
+            break;
+        }
+        if !is_blank {
+            result = begin;
+        }
+    }
+    line_begin + result
+}
+
+/// End of the word after offset. From the end of line, goes to the beginning of next one.
+pub fn word_end_after(rope : &Rope, offset : usize) -> usize {
+    let len_chars = rope.len_chars();
+    if offset >= len_chars {
+        return len_chars;
+    }
+
+    let line = rope.char_to_line(offset);
+    let line_begin = rope.line_to_char(line);
+    let line_end = line_end_without_newline(rope, line);
+    if offset >= line_end {
+        return if line + 1 < rope.len_lines() { rope.line_to_char(line + 1) } else { len_chars };
+    }
+
+    let pos = offset - line_begin;
+    for (_, end, is_blank) in line_segments(rope, line) {
+        if end > pos && !is_blank {
+            return line_begin + end;
+        }
+    }
+    line_end
+}
+
+/// First non-blank char of line, or beginning of line if cursor is already there.
+pub fn smart_line_start(rope : &Rope, offset : usize) -> usize {
+    let line = rope.char_to_line(offset);
+    let line_begin = rope.line_to_char(line);
+    let line_end = line_end_without_newline(rope, line);
+
+    let mut first_non_blank = line_begin;
+    while first_non_blank < line_end && rope.char(first_non_blank).is_whitespace() {
+        first_non_blank += 1;
+    }
+
+    if offset == first_non_blank {
+        line_begin
+    } else {
+        first_non_blank
+    }
+}
+
+/// End of line (without newline), or end of it's last non-blank char if cursor is already there.
+pub fn smart_line_end(rope : &Rope, offset : usize) -> usize {
+    let line = rope.char_to_line(offset);
+    let line_begin = rope.line_to_char(line);
+    let line_end = line_end_without_newline(rope, line);
+
+    let mut last_non_blank = line_end;
+    while last_non_blank > line_begin && rope.char(last_non_blank - 1).is_whitespace() {
+        last_non_blank -= 1;
+    }
+
+    if offset == line_end {
+        last_non_blank
+    } else {
+        line_end
+    }
+}
+
+/// Where delete-to-line-end stops: end of line, or beginning of next one if cursor is at the end
+/// already (so lines get joined).
+pub fn line_end_or_next_line(rope : &Rope, offset : usize) -> usize {
+    let line = rope.char_to_line(offset);
+    let line_end = line_end_without_newline(rope, line);
+    if offset < line_end {
+        line_end
+    } else if line + 1 < rope.len_lines() {
+        rope.line_to_char(line + 1)
+    } else {
+        rope.len_chars()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_movement_skips_whitespace_and_stops_at_line_ends() {
+        let rope = Rope::from_str("fn main() {\n    let x = 1;\n}");
+
+        assert_eq!(word_end_after(&rope, 0), 2); // "fn"
+        assert_eq!(word_end_after(&rope, 2), 7); // "main"
+        assert_eq!(word_end_after(&rope, 11), 12); // over newline
+        assert_eq!(word_end_after(&rope, 12), 19); // "let", skipping indentation
+
+        assert_eq!(word_start_before(&rope, 19), 16);
+        assert_eq!(word_start_before(&rope, 16), 12);
+        assert_eq!(word_start_before(&rope, 12), 11);
+        assert_eq!(word_start_before(&rope, 3), 0);
+    }
+
+    #[test]
+    fn grapheme_movement_skips_combining_marks_and_crlf() {
+        let rope = Rope::from_str("e\u{301}x\r\ny");
+
+        assert_eq!(grapheme_end_after(&rope, 0), 2);
+        assert_eq!(grapheme_end_after(&rope, 3), 5);
+        assert_eq!(grapheme_start_before(&rope, 2), 0);
+        assert_eq!(grapheme_start_before(&rope, 5), 3);
+        assert_eq!(grapheme_start_before(&rope, 6), 5);
+    }
+
+    #[test]
+    fn smart_home_and_end_toggle() {
+        let rope = Rope::from_str("a\n    foo();  \nb");
+
+        assert_eq!(smart_line_start(&rope, 9), 6);
+        assert_eq!(smart_line_start(&rope, 6), 2);
+        assert_eq!(smart_line_start(&rope, 2), 6);
+
+        assert_eq!(smart_line_end(&rope, 6), 14);
+        assert_eq!(smart_line_end(&rope, 14), 12);
+        assert_eq!(smart_line_end(&rope, 12), 14);
+    }
+}